@@ -0,0 +1,74 @@
+use num_complex::Complex64;
+
+/// Applies an arithmetic operator to two complex numbers.
+pub fn apply(operator: &str, a: Complex64, b: Complex64) -> Option<Complex64> {
+    match operator {
+        "+" => Some(a + b),
+        "-" => Some(a - b),
+        "*" => Some(a * b),
+        "/" => Some(a / b),
+        // Integer powers by repeated multiplication stay exact, e.g. `i ^^ 2` is `-1`.
+        "^^" if b.im == 0.0 && b.re.fract() == 0.0 && b.re.abs() <= i32::MAX as f64 => Some(a.powi(b.re as i32)),
+        "^^" if a == Complex64::new(0.0, 0.0) && b.re > 0.0 => Some(a),
+        "^^" => Some(a.powc(b)),
+        _ => None,
+    }
+}
+
+/// Evaluates a complex-aware built-in, or `None` if the name or argument count
+/// does not fit.
+pub fn apply_function(name: &str, arguments: &[Complex64]) -> Option<Complex64> {
+    let result = match (name, arguments) {
+        ("abs", [z]) => Complex64::from(z.norm()),
+        ("arg", [z]) => Complex64::from(z.arg()),
+        ("conj", [z]) => z.conj(),
+        ("re", [z]) => Complex64::from(z.re),
+        ("im", [z]) => Complex64::from(z.im),
+        ("exp", [z]) => z.exp(),
+        ("ln", [z]) => z.ln(),
+        ("sqrt", [z]) => z.sqrt(),
+        ("sin", [z]) => z.sin(),
+        ("cos", [z]) => z.cos(),
+        ("tan", [z]) => z.tan(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Writes a complex number the way it is typed, e.g. `3+4i`, `-2i` or `1-i`.
+pub fn format(z: &Complex64) -> String {
+    let imaginary = if z.im.abs() == 1.0 { String::from("i") } else { format!("{}i", z.im.abs()) };
+    match (z.re == 0.0, z.im.is_sign_negative()) {
+        (true, false) => imaginary,
+        (true, true) => format!("-{}", imaginary),
+        (false, false) => format!("{}+{}", z.re, imaginary),
+        (false, true) => format!("{}-{}", z.re, imaginary),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_on_three_plus_four_i() {
+        let z = Complex64::new(3.0, 4.0);
+        assert_eq!(apply("+", z, Complex64::new(1.0, -1.0)), Some(Complex64::new(4.0, 3.0)));
+        assert_eq!(apply("*", z, z.conj()), Some(Complex64::new(25.0, 0.0)));
+        assert_eq!(apply("^^", z, Complex64::new(2.0, 0.0)), Some(Complex64::new(-7.0, 24.0)));
+        assert_eq!(apply("^^", Complex64::i(), Complex64::new(2.0, 0.0)), Some(Complex64::new(-1.0, 0.0)));
+        let quotient = apply("/", z, Complex64::new(1.0, 2.0)).unwrap();
+        assert!((quotient - Complex64::new(2.2, -0.4)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn built_ins_and_printing() {
+        let z = Complex64::new(3.0, 4.0);
+        assert_eq!(apply_function("abs", &[z]), Some(Complex64::new(5.0, 0.0)));
+        assert_eq!(apply_function("im", &[z]), Some(Complex64::new(4.0, 0.0)));
+        assert_eq!(apply_function("sqrt", &[Complex64::new(-4.0, 0.0)]), Some(Complex64::new(0.0, 2.0)));
+        assert_eq!(format(&z), "3+4i");
+        assert_eq!(format(&z.conj()), "3-4i");
+        assert_eq!(format(&-Complex64::i()), "-i");
+    }
+}
@@ -0,0 +1,347 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+
+/// Largest power `^^` raises a decimal to exactly.
+const MAX_EXPONENT: u32 = 10_000;
+
+/// Largest power of ten a decimal may be scaled by either way, which keeps
+/// aligning two decimals for a sum cheap.
+const MAX_SCALE: i64 = 100_000;
+
+fn out_of_range() -> String {
+    String::from("Error. Decimal exponent out of range.")
+}
+
+fn division_by_zero() -> String {
+    String::from("Error. Division by zero.")
+}
+
+/// How decimal results are rounded to the working precision.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Rounding {
+    /// Ties go to the even neighbour, as in banker's rounding.
+    #[default]
+    HalfEven,
+    /// Ties go away from zero.
+    HalfUp,
+    /// Drops the extra digits, rounding towards zero.
+    Down,
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Rounding> {
+        match name {
+            "half-even" => Some(Rounding::HalfEven),
+            "half-up" => Some(Rounding::HalfUp),
+            "down" => Some(Rounding::Down),
+            _ => None,
+        }
+    }
+}
+
+/// The significant digits every decimal result is rounded to, and how.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Context {
+    pub precision: u32,
+    pub rounding: Rounding,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context { precision: 28, rounding: Rounding::HalfEven }
+    }
+}
+
+/// The number `coefficient * 10 ^ exponent`. Trailing zeros are kept, so `1.10`
+/// and `1.1` are equal but print differently.
+#[derive(Clone, Debug)]
+pub struct Decimal {
+    coefficient: BigInt,
+    exponent: i64,
+}
+
+fn power_of_ten(power: u64) -> BigInt {
+    BigInt::from(10).pow(power)
+}
+
+fn digit_count(n: &BigInt) -> i64 {
+    n.magnitude().to_string().len() as i64
+}
+
+impl Decimal {
+    /// Reads a literal such as `12.50` digit for digit.
+    pub fn parse(literal: &str) -> Option<Decimal> {
+        let (literal, exponent) = match literal.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok().filter(|exponent| exponent.unsigned_abs() <= MAX_SCALE as u64)?),
+            None => (literal, 0),
+        };
+        let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+        let digits = format!("{}{}", whole, fraction);
+        if digits.is_empty() || !digits.chars().all(|digit| digit.is_ascii_digit()) {
+            return None;
+        }
+        Some(Decimal { coefficient: digits.parse().ok()?, exponent: exponent.checked_sub(fraction.len() as i64)? })
+    }
+
+    pub fn from_integer(value: BigInt) -> Decimal {
+        Decimal { coefficient: value, exponent: 0 }
+    }
+
+    /// The shortest decimal that reads back as `value`.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        let magnitude = Decimal::parse(format!("{}", value.abs()).as_str())?;
+        Some(if value < 0.0 { magnitude.negate() } else { magnitude })
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.coefficient, self.exponent).parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.coefficient.is_negative()
+    }
+
+    pub fn negate(&self) -> Decimal {
+        Decimal { coefficient: -&self.coefficient, exponent: self.exponent }
+    }
+
+    /// Rounds `coefficient * 10 ^ exponent` to a multiple of `10 ^ target`.
+    /// `inexact` says nonzero digits were already dropped below `exponent`, which
+    /// turns an apparent tie into a value just above it.
+    fn rounded_to(coefficient: BigInt, exponent: i64, target: i64, inexact: bool, rounding: Rounding) -> Decimal {
+        if target <= exponent {
+            let coefficient = coefficient * power_of_ten((exponent - target) as u64);
+            return Decimal { coefficient, exponent: target };
+        }
+        let divisor = power_of_ten((target - exponent) as u64);
+        let (mut quotient, remainder) = coefficient.div_rem(&divisor);
+        let twice = remainder.magnitude() * 2u32;
+        let away_from_zero = match rounding {
+            Rounding::Down => false,
+            Rounding::HalfUp => twice >= *divisor.magnitude(),
+            Rounding::HalfEven => {
+                twice > *divisor.magnitude() || (twice == *divisor.magnitude() && (inexact || quotient.is_odd()))
+            }
+        };
+        if away_from_zero {
+            quotient += if coefficient.is_negative() { -1 } else { 1 };
+        }
+        Decimal { coefficient: quotient, exponent: target }
+    }
+
+    /// Rounds to the context's number of significant digits.
+    fn with_precision(coefficient: BigInt, exponent: i64, inexact: bool, context: &Context) -> Decimal {
+        let excess = digit_count(&coefficient) - context.precision as i64;
+        if excess <= 0 && !inexact {
+            return Decimal { coefficient, exponent };
+        }
+        let mut rounded = Decimal::rounded_to(coefficient, exponent, exponent + excess.max(0), inexact, context.rounding);
+        // Rounding 999 up gives 1000, one digit too many but exactly divisible.
+        if digit_count(&rounded.coefficient) > context.precision as i64 {
+            rounded.coefficient /= 10;
+            rounded.exponent += 1;
+        }
+        rounded
+    }
+
+    /// Both coefficients scaled to the smaller exponent.
+    fn aligned(&self, other: &Decimal) -> (BigInt, BigInt, i64) {
+        let exponent = self.exponent.min(other.exponent);
+        let a = &self.coefficient * power_of_ten((self.exponent - exponent) as u64);
+        let b = &other.coefficient * power_of_ten((other.exponent - exponent) as u64);
+        (a, b, exponent)
+    }
+
+    pub fn add(&self, other: &Decimal, context: &Context) -> Decimal {
+        let (a, b, exponent) = self.aligned(other);
+        Decimal::with_precision(a + b, exponent, false, context)
+    }
+
+    pub fn multiply(&self, other: &Decimal, context: &Context) -> Result<Decimal, String> {
+        let exponent = self.exponent.checked_add(other.exponent).ok_or_else(out_of_range)?;
+        Ok(Decimal::with_precision(&self.coefficient * &other.coefficient, exponent, false, context))
+    }
+
+    pub fn divide(&self, other: &Decimal, context: &Context) -> Option<Decimal> {
+        if other.coefficient.is_zero() {
+            return None;
+        }
+        let ideal_exponent = self.exponent - other.exponent;
+        if self.coefficient.is_zero() {
+            return Some(Decimal { coefficient: BigInt::zero(), exponent: ideal_exponent });
+        }
+        // Enough digits that the quotient has at least one beyond the precision.
+        let shift = (context.precision as i64 + 1 + digit_count(&other.coefficient) - digit_count(&self.coefficient)).max(0);
+        let numerator = &self.coefficient * power_of_ten(shift as u64);
+        let (mut quotient, remainder) = numerator.div_rem(&other.coefficient);
+        let mut exponent = ideal_exponent - shift;
+        let exact = remainder.is_zero();
+        while exact && exponent < ideal_exponent && (&quotient % 10u32).is_zero() {
+            quotient /= 10;
+            exponent += 1;
+        }
+        Some(Decimal::with_precision(quotient, exponent, !exact, context))
+    }
+
+    /// The quotient rounded down and the remainder, which has the sign of `other`.
+    pub fn divide_floor(&self, other: &Decimal, context: &Context) -> Option<(Decimal, Decimal)> {
+        if other.coefficient.is_zero() {
+            return None;
+        }
+        let (a, b, exponent) = self.aligned(other);
+        let (quotient, remainder) = a.div_mod_floor(&b);
+        Some((Decimal::with_precision(quotient, 0, false, context), Decimal::with_precision(remainder, exponent, false, context)))
+    }
+
+    /// `self ^^ power` for an integer power. Fails for other powers, which have
+    /// no exact decimal value, for powers that are too large, and for zero to a
+    /// negative power.
+    pub fn power(&self, power: &Decimal, context: &Context) -> Result<Decimal, String> {
+        let power = power.to_integer().and_then(|power| power.to_i64())
+            .ok_or_else(|| String::from("Error. Decimal powers must be whole numbers."))?;
+        let magnitude = u32::try_from(power.unsigned_abs()).ok().filter(|magnitude| *magnitude <= MAX_EXPONENT)
+            .ok_or_else(out_of_range)?;
+        let exponent = self.exponent.checked_mul(magnitude as i64)
+            .filter(|exponent| exponent.unsigned_abs() <= 2 * MAX_SCALE as u64)
+            .ok_or_else(out_of_range)?;
+        let exact = Decimal {
+            coefficient: Pow::pow(&self.coefficient, magnitude),
+            exponent,
+        };
+        if power < 0 {
+            return Decimal::from_integer(BigInt::from(1)).divide(&exact, context).ok_or_else(division_by_zero);
+        }
+        Ok(Decimal::with_precision(exact.coefficient, exact.exponent, false, context))
+    }
+
+    /// The square root rounded to the context's precision, with trailing zeros
+    /// dropped when it is exact, so `sqrt(4)` is `2`.
+    pub fn square_root(&self, context: &Context) -> Result<Decimal, String> {
+        if self.is_negative() {
+            return Err(String::from("Error. Cannot take the square root of a negative decimal."));
+        }
+        let ideal_exponent = Integer::div_floor(&self.exponent, &2);
+        let (mut coefficient, mut exponent) = (self.coefficient.clone(), self.exponent);
+        // An even exponent, and enough digits for one beyond the precision.
+        let mut shift = (2 * (context.precision as i64 + 1) - digit_count(&coefficient)).max(0);
+        if (exponent - shift).rem_euclid(2) == 1 {
+            shift += 1;
+        }
+        coefficient *= power_of_ten(shift as u64);
+        exponent -= shift;
+        let mut root = coefficient.sqrt();
+        let exact = &root * &root == coefficient;
+        let mut exponent = exponent / 2;
+        while exact && exponent < ideal_exponent && !root.is_zero() && (&root % 10u32).is_zero() {
+            root /= 10;
+            exponent += 1;
+        }
+        Decimal::with_precision(root, exponent, !exact, context).in_range()
+    }
+
+    /// The largest whole number not above the value.
+    pub fn floor(&self) -> Decimal {
+        if self.exponent >= 0 {
+            return self.clone();
+        }
+        Decimal::from_integer(self.coefficient.div_floor(&power_of_ten(self.exponent.unsigned_abs())))
+    }
+
+    /// Fails if the decimal is scaled beyond `MAX_SCALE`.
+    fn in_range(self) -> Result<Decimal, String> {
+        if self.exponent.unsigned_abs() > MAX_SCALE as u64 {
+            return Err(out_of_range());
+        }
+        Ok(self)
+    }
+
+    /// The value, if it is a whole number.
+    pub fn to_integer(&self) -> Option<BigInt> {
+        if self.exponent >= 0 {
+            return Some(&self.coefficient * power_of_ten(self.exponent as u64));
+        }
+        let (quotient, remainder) = self.coefficient.div_rem(&power_of_ten(self.exponent.unsigned_abs()));
+        remainder.is_zero().then_some(quotient)
+    }
+
+    /// `self` rounded to exactly `places` digits after the point.
+    pub fn to_places(&self, places: u32, rounding: Rounding) -> Decimal {
+        Decimal::rounded_to(self.coefficient.clone(), self.exponent, -(places as i64), false, rounding)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b, _) = self.aligned(other);
+        a == b
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.aligned(other);
+        a.cmp(&b)
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.coefficient.magnitude().to_string();
+        if self.exponent >= 0 {
+            return write!(f, "{}{}{}", sign, digits, "0".repeat(self.exponent as usize));
+        }
+        let places = self.exponent.unsigned_abs() as usize;
+        let padded = format!("{:0>width$}", digits, width = places + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - places);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Applies `+ - * / // %` or an integer `^^` in `context`. Gives `None` for other
+/// operators, and fails for division by zero, for powers with no exact decimal
+/// value, and when the result would be scaled beyond `MAX_SCALE`.
+pub fn apply(operator: &str, a: &Decimal, b: &Decimal, context: &Context) -> Result<Option<Decimal>, String> {
+    let result = match operator {
+        "+" => a.add(b, context),
+        "-" => a.add(&b.negate(), context),
+        "*" => a.multiply(b, context)?,
+        "/" => a.divide(b, context).ok_or_else(division_by_zero)?,
+        "//" => a.divide_floor(b, context).ok_or_else(division_by_zero)?.0,
+        "%" => a.divide_floor(b, context).ok_or_else(division_by_zero)?.1,
+        "^^" => a.power(b, context)?,
+        _ => return Ok(None),
+    };
+    result.in_range().map(Some)
+}
+
+/// Evaluates the built-ins that have exact decimal results in `context`, or gives
+/// `None` for the others.
+pub fn apply_function(name: &str, arguments: &[Decimal], context: &Context) -> Result<Option<Decimal>, String> {
+    let result = match (name, arguments) {
+        ("sqrt", [x]) => x.square_root(context)?,
+        ("abs", [x]) if x.is_negative() => x.negate(),
+        ("abs", [x]) => x.clone(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.negate().floor().negate(),
+        ("round", [x]) => x.to_places(0, context.rounding),
+        ("min", [first, rest @ ..]) => rest.iter().fold(first, |a, b| a.min(b)).clone(),
+        ("max", [first, rest @ ..]) => rest.iter().fold(first, |a, b| a.max(b)).clone(),
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
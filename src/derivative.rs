@@ -0,0 +1,187 @@
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::TokenType::{Function, Operator, VariableName};
+
+/// Differentiates `tree` with respect to `variable`.
+///
+/// Terms that are trivially zero or one are dropped while the derivative is built,
+/// so `derive(x ^^ 3, "x")` is `3 * x ^^ 2` rather than `3 * x ^^ (3 - 1) * 1`.
+pub fn derive(tree: &SyntaxTreeNode, variable: &str) -> Result<SyntaxTreeNode, String> {
+    if !tree.contains_variable(variable) {
+        return Ok(SyntaxTreeNode::number(0.0));
+    }
+
+    let name = tree.value_as_string();
+    let children = tree.children();
+    match tree.token_type() {
+        VariableName => Ok(SyntaxTreeNode::number(1.0)),
+
+        Operator if children.len() == 1 && name == "NEGATE" => Ok(negate(derive(&children[0], variable)?)),
+
+        Operator if children.len() == 2 => {
+            let (u, v) = (&children[0], &children[1]);
+            match name.as_str() {
+                "+" => Ok(add(derive(u, variable)?, derive(v, variable)?)),
+                "-" => Ok(subtract(derive(u, variable)?, derive(v, variable)?)),
+                "*" => Ok(add(
+                    multiply(derive(u, variable)?, v.clone()),
+                    multiply(u.clone(), derive(v, variable)?),
+                )),
+                "/" => Ok(divide(
+                    subtract(multiply(derive(u, variable)?, v.clone()), multiply(u.clone(), derive(v, variable)?)),
+                    power(v.clone(), SyntaxTreeNode::number(2.0)),
+                )),
+                "^^" => derive_power(tree, u, v, variable),
+                _ => Err(format!("Error. Cannot differentiate the {} operator.", name)),
+            }
+        }
+
+        Function if children.len() == 1 => {
+            let u = &children[0];
+            let outer = function_derivative(name.as_str(), u)?;
+            Ok(multiply(outer, derive(u, variable)?))
+        }
+
+        _ => Err(format!("Error. Cannot differentiate {}.", name)),
+    }
+}
+
+fn derive_power(tree: &SyntaxTreeNode, u: &SyntaxTreeNode, v: &SyntaxTreeNode, variable: &str) -> Result<SyntaxTreeNode, String> {
+    // d(u ^^ n) = n * u ^^ (n - 1) * du
+    if !v.contains_variable(variable) {
+        let exponent = match v.as_number() {
+            Some(n) => SyntaxTreeNode::number(n - 1.0),
+            None => subtract(v.clone(), SyntaxTreeNode::number(1.0)),
+        };
+        return Ok(multiply(multiply(v.clone(), power(u.clone(), exponent)), derive(u, variable)?));
+    }
+
+    // d(a ^^ v) = a ^^ v * ln(a) * dv
+    if !u.contains_variable(variable) {
+        return Ok(multiply(multiply(tree.clone(), ln(u.clone())), derive(v, variable)?));
+    }
+
+    // d(u ^^ v) = u ^^ v * (dv * ln(u) + v * du / u)
+    Ok(multiply(tree.clone(), add(
+        multiply(derive(v, variable)?, ln(u.clone())),
+        divide(multiply(v.clone(), derive(u, variable)?), u.clone()),
+    )))
+}
+
+/// `f'(u)` for the built-in functions of one argument.
+fn function_derivative(name: &str, u: &SyntaxTreeNode) -> Result<SyntaxTreeNode, String> {
+    let u = u.clone();
+    let one = || SyntaxTreeNode::number(1.0);
+    let two = || SyntaxTreeNode::number(2.0);
+    let call = |function: &str, argument: SyntaxTreeNode| SyntaxTreeNode::function(function, vec![argument]);
+    let derivative = match name {
+        "sin" => call("cos", u),
+        "cos" => negate(call("sin", u)),
+        "tan" => divide(one(), power(call("cos", u), two())),
+        "exp" => call("exp", u),
+        "ln" => divide(one(), u),
+        "log" => divide(one(), multiply(u, ln(SyntaxTreeNode::number(10.0)))),
+        "sqrt" => divide(one(), multiply(two(), call("sqrt", u))),
+        "sinh" => call("cosh", u),
+        "cosh" => call("sinh", u),
+        "tanh" => subtract(one(), power(call("tanh", u), two())),
+        "asin" => divide(one(), call("sqrt", subtract(one(), power(u, two())))),
+        "acos" => negate(divide(one(), call("sqrt", subtract(one(), power(u, two()))))),
+        "atan" => divide(one(), add(one(), power(u, two()))),
+        "abs" => divide(u.clone(), call("abs", u)),
+        _ => return Err(format!("Error. Unknown derivative of {}.", name)),
+    };
+    Ok(derivative)
+}
+
+/// `ln(argument)`, which is 1 for the constant `e`.
+fn ln(argument: SyntaxTreeNode) -> SyntaxTreeNode {
+    if argument.token_type() == VariableName && argument.value_as_string() == "e" {
+        return SyntaxTreeNode::number(1.0);
+    }
+    SyntaxTreeNode::function("ln", vec![argument])
+}
+
+fn is_zero(tree: &SyntaxTreeNode) -> bool {
+    tree.as_number() == Some(0.0)
+}
+
+fn is_one(tree: &SyntaxTreeNode) -> bool {
+    tree.as_number() == Some(1.0)
+}
+
+fn add(a: SyntaxTreeNode, b: SyntaxTreeNode) -> SyntaxTreeNode {
+    if is_zero(&a) {
+        return b;
+    }
+    if is_zero(&b) {
+        return a;
+    }
+    SyntaxTreeNode::operator("+", vec![a, b])
+}
+
+fn subtract(a: SyntaxTreeNode, b: SyntaxTreeNode) -> SyntaxTreeNode {
+    if is_zero(&b) {
+        return a;
+    }
+    if is_zero(&a) {
+        return negate(b);
+    }
+    SyntaxTreeNode::operator("-", vec![a, b])
+}
+
+fn multiply(a: SyntaxTreeNode, b: SyntaxTreeNode) -> SyntaxTreeNode {
+    if is_zero(&a) || is_zero(&b) {
+        return SyntaxTreeNode::number(0.0);
+    }
+    if is_one(&a) {
+        return b;
+    }
+    if is_one(&b) {
+        return a;
+    }
+    SyntaxTreeNode::operator("*", vec![a, b])
+}
+
+fn divide(a: SyntaxTreeNode, b: SyntaxTreeNode) -> SyntaxTreeNode {
+    if is_zero(&a) {
+        return SyntaxTreeNode::number(0.0);
+    }
+    if is_one(&b) {
+        return a;
+    }
+    SyntaxTreeNode::operator("/", vec![a, b])
+}
+
+fn power(base: SyntaxTreeNode, exponent: SyntaxTreeNode) -> SyntaxTreeNode {
+    if is_one(&exponent) {
+        return base;
+    }
+    SyntaxTreeNode::operator("^^", vec![base, exponent])
+}
+
+fn negate(a: SyntaxTreeNode) -> SyntaxTreeNode {
+    if let Some(n) = a.as_number() {
+        return SyntaxTreeNode::number(-n);
+    }
+    if a.is_operator("NEGATE") {
+        return a.children()[0].clone();
+    }
+    SyntaxTreeNode::operator("NEGATE", vec![a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntaxtree::string_to_tree;
+
+    fn derivative(source: &str, variable: &str) -> String {
+        derive(&string_to_tree(source).unwrap(), variable).unwrap().to_string()
+    }
+
+    #[test]
+    fn e_is_the_natural_base() {
+        assert_eq!(derivative("e ^^ x", "x"), "e ^^ x");
+        assert_eq!(derivative("2 ^^ x", "x"), "2 ^^ x * ln(2)");
+        assert_eq!(derivative("x ^^ e", "x"), "e * x ^^ (e - 1)");
+    }
+}
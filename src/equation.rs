@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use crate::derivative::derive;
+use crate::numeric;
+use crate::operators::chain_links;
+use crate::simplify::simplify;
+use crate::solver::{constant, evaluate_tree};
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::TokenType::Operator;
+
+const TOLERANCE: f64 = 1e-12;
+
+/// Starting points tried, in order, for Newton's method.
+const NEWTON_STARTS: [f64; 4] = [1.0, 0.0, -1.0, 10.0];
+
+/// Points at which an equation is sampled to tell whether its sides differ by a constant.
+const SAMPLES: [f64; 5] = [-2.3, -0.7, 0.4, 1.9, 3.6];
+
+const INEQUALITIES: [&str; 5] = ["!=", "<", "<=", ">", ">="];
+
+/// Solves `equation` for `unknown` and returns the answer as an equation, e.g.
+/// `x = 4` for `2 * x + 3 = 11`. An expression without `=` is solved for zero,
+/// and `==` is read as `=`. Inequalities are rejected.
+///
+/// Unknowns that appear once under `+ - * /` or linearly are isolated
+/// symbolically, so other variables may remain in the answer. Otherwise the
+/// root is found numerically, with Newton's method and then Brent's method.
+pub fn solve_equation(equation: &SyntaxTreeNode, unknown: Option<&str>) -> Result<SyntaxTreeNode, String> {
+    let relation = if equation.token_type() == Operator { equation.value_as_string() } else { String::new() };
+    let (left, right) = if relation == "=" || relation == "==" {
+        (equation.children()[0].clone(), equation.children()[1].clone())
+    } else if INEQUALITIES.contains(&relation.as_str()) || chain_links(relation.as_str()).is_some() {
+        return Err(format!("Error. Cannot solve the inequality {}; use = for an equation.", relation));
+    } else {
+        (equation.clone(), SyntaxTreeNode::number(0.0))
+    };
+
+    let unknown = match unknown {
+        Some(unknown) => String::from(unknown),
+        None => find_unknown(equation)?,
+    };
+    if !equation.contains_variable(unknown.as_str()) {
+        return Err(format!("Error. {} does not appear in the equation.", unknown));
+    }
+
+    check_determined(&left, &right, unknown.as_str())?;
+
+    let solution = match isolate_symbolically(&left, &right, unknown.as_str()) {
+        Some(solution) => check_solution(&left, &right, unknown.as_str(), simplify(&solution))?,
+        None => SyntaxTreeNode::number(solve_numerically(&left, &right, unknown.as_str())?),
+    };
+    Ok(SyntaxTreeNode::operator("=", vec![SyntaxTreeNode::variable(unknown.as_str()), solution]))
+}
+
+fn find_unknown(equation: &SyntaxTreeNode) -> Result<String, String> {
+    let mut unknowns: Vec<String> = equation.variables().into_iter()
+        .filter(|name| constant(name.as_str()).is_none())
+        .collect();
+    if unknowns.len() != 1 {
+        return Err(String::from("Error. Name the unknown to solve for."));
+    }
+    Ok(unknowns.remove(0))
+}
+
+/// Rejects equations whose sides differ by a constant, which hold for every
+/// value of `unknown` or for none.
+fn check_determined(left: &SyntaxTreeNode, right: &SyntaxTreeNode, unknown: &str) -> Result<(), String> {
+    let difference = simplify(&SyntaxTreeNode::operator("-", vec![left.clone(), right.clone()]));
+    let constant = if difference.contains_variable(unknown) {
+        let values: Option<Vec<f64>> = SAMPLES.iter()
+            .map(|x| evaluate_tree(&difference, &HashMap::from([(String::from(unknown), *x)])).ok())
+            .collect();
+        values.filter(|values| values.iter().all(|value| (value - values[0]).abs() <= 1e-9 * (1.0 + values[0].abs())))
+            .map(|values| values[0])
+    } else {
+        let value = evaluate_tree(&difference, &HashMap::new())
+            .map_err(|_| format!("Error. {} cancels out of the equation.", unknown))?;
+        Some(value)
+    };
+    match constant {
+        Some(value) if value.abs() <= 1e-9 => Err(format!("Error. The equation holds for every {}.", unknown)),
+        Some(_) => Err(String::from("Error. The equation has no solution.")),
+        None => Ok(()),
+    }
+}
+
+/// Plugs a symbolic solution without other variables back into the equation, to
+/// catch inverses that do not apply, such as `x = 1 / 0` for `1 / x = 0`.
+fn check_solution(left: &SyntaxTreeNode, right: &SyntaxTreeNode, unknown: &str, solution: SyntaxTreeNode) -> Result<SyntaxTreeNode, String> {
+    let value = match evaluate_tree(&solution, &HashMap::new()) {
+        Ok(value) => value,
+        Err(_) => return Ok(solution),
+    };
+    let bindings = HashMap::from([(String::from(unknown), value)]);
+    let sides = evaluate_tree(left, &bindings).and_then(|left| Ok((left, evaluate_tree(right, &bindings)?)));
+    match sides {
+        Ok((left, right)) if value.is_finite() && (left - right).abs() <= 1e-9 * (1.0 + left.abs() + right.abs()) => Ok(solution),
+        _ => Err(String::from("Error. The equation has no solution.")),
+    }
+}
+
+fn occurrences(tree: &SyntaxTreeNode, unknown: &str) -> usize {
+    if tree.children().is_empty() {
+        return if tree.contains_variable(unknown) { 1 } else { 0 };
+    }
+    tree.children().iter().map(|child| occurrences(child, unknown)).sum()
+}
+
+fn isolate_symbolically(left: &SyntaxTreeNode, right: &SyntaxTreeNode, unknown: &str) -> Option<SyntaxTreeNode> {
+    let (side, other) = if left.contains_variable(unknown) { (left, right) } else { (right, left) };
+    if occurrences(left, unknown) + occurrences(right, unknown) == 1 {
+        if let Some(solution) = isolate(side, other.clone(), unknown) {
+            return Some(solution);
+        }
+    }
+    solve_linear(left, right, unknown)
+}
+
+/// Undoes the operations on the path from `side` down to the single occurrence of
+/// `unknown`, applying their inverses to `other`.
+fn isolate(side: &SyntaxTreeNode, other: SyntaxTreeNode, unknown: &str) -> Option<SyntaxTreeNode> {
+    if side.children().is_empty() {
+        return Some(other);
+    }
+    let operator = |name: &str, a: SyntaxTreeNode, b: SyntaxTreeNode| SyntaxTreeNode::operator(name, vec![a, b]);
+    if side.is_operator("NEGATE") {
+        return isolate(&side.children()[0], SyntaxTreeNode::operator("NEGATE", vec![other]), unknown);
+    }
+    if side.children().len() != 2 {
+        return None;
+    }
+    let (u, v) = (side.children()[0].clone(), side.children()[1].clone());
+    let in_left = u.contains_variable(unknown);
+    let name = side.value_as_string();
+    match (name.as_str(), in_left) {
+        ("+", true) => isolate(&u, operator("-", other, v), unknown),
+        ("+", false) => isolate(&v, operator("-", other, u), unknown),
+        ("-", true) => isolate(&u, operator("+", other, v), unknown),
+        ("-", false) => isolate(&v, operator("-", u, other), unknown),
+        ("*", true) => isolate(&u, operator("/", other, v), unknown),
+        ("*", false) => isolate(&v, operator("/", other, u), unknown),
+        ("/", true) => isolate(&u, operator("*", other, v), unknown),
+        ("/", false) => isolate(&v, operator("/", u, other), unknown),
+        _ => None,
+    }
+}
+
+/// For `a * x + b = 0` with `a` free of `x`, `x = -b / a`.
+fn solve_linear(left: &SyntaxTreeNode, right: &SyntaxTreeNode, unknown: &str) -> Option<SyntaxTreeNode> {
+    let difference = SyntaxTreeNode::operator("-", vec![left.clone(), right.clone()]);
+    let slope = simplify(&derive(&difference, unknown).ok()?);
+    if slope.contains_variable(unknown) || slope.as_number() == Some(0.0) {
+        return None;
+    }
+    let intercept = simplify(&difference.substitute(unknown, &SyntaxTreeNode::number(0.0)));
+    Some(SyntaxTreeNode::operator("/", vec![SyntaxTreeNode::operator("NEGATE", vec![intercept]), slope]))
+}
+
+fn solve_numerically(left: &SyntaxTreeNode, right: &SyntaxTreeNode, unknown: &str) -> Result<f64, String> {
+    let difference = SyntaxTreeNode::operator("-", vec![left.clone(), right.clone()]);
+    let at = |tree: &SyntaxTreeNode, x: f64| {
+        let bindings = HashMap::from([(String::from(unknown), x)]);
+        evaluate_tree(tree, &bindings)
+    };
+    // Reports unbound variables and unknown functions before searching.
+    at(&difference, 0.0)?;
+    let f = |x: f64| at(&difference, x).unwrap_or(f64::NAN);
+
+    let slope = derive(&difference, unknown).map(|slope| simplify(&slope));
+    let derivative = |x: f64| match &slope {
+        Ok(slope) => at(slope, x).unwrap_or(f64::NAN),
+        Err(_) => {
+            let h = 1e-6 * (1.0 + x.abs());
+            (f(x + h) - f(x - h)) / (2.0 * h)
+        }
+    };
+    let is_root = |x: f64| f(x).abs() <= 1e-9 * (1.0 + x.abs());
+
+    let newton = NEWTON_STARTS.iter()
+        .filter_map(|start| numeric::newton(f, derivative, *start, TOLERANCE))
+        .find(|x| is_root(*x));
+    if let Some(root) = newton {
+        return Ok(root);
+    }
+
+    numeric::find_bracket(f, 0.0)
+        .and_then(|(lower, upper)| numeric::brent(f, lower, upper, TOLERANCE))
+        .filter(|x| is_root(*x))
+        .ok_or_else(|| String::from("Error. No solution found."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntaxtree::string_to_tree;
+
+    fn solved(equation: &str) -> Result<String, String> {
+        solve_equation(&string_to_tree(equation)?, None).map(|solution| solution.to_string())
+    }
+
+    #[test]
+    fn isolates_the_unknown() {
+        assert_eq!(solved("2 * x + 3 = 11"), Ok(String::from("x = 4")));
+        assert_eq!(solved("x == 4"), Ok(String::from("x = 4")));
+    }
+
+    #[test]
+    fn rejects_equations_without_a_single_solution() {
+        let no_solution = Err(String::from("Error. The equation has no solution."));
+        assert_eq!(solved("1 / x = 0"), no_solution);
+        assert_eq!(solved("x + 1 = x"), no_solution);
+        assert_eq!(solved("2 * x = 2 * x"), Err(String::from("Error. The equation holds for every x.")));
+        assert!(solved("x < 4").is_err());
+    }
+}
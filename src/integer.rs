@@ -0,0 +1,141 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Pow, Signed, ToPrimitive, Zero};
+
+/// Largest result, in bits, `^^` computes exactly; bigger ones fall back to `f64`.
+const MAX_BITS: u64 = 1_000_000;
+
+/// Largest argument `!` and `factorial` compute exactly.
+const MAX_FACTORIAL: u32 = 20_000;
+
+/// Bases for Miller–Rabin. Together they decide every number below 3.3e24 and
+/// make a wrong answer vanishingly unlikely above that.
+const WITNESSES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Reads a literal made only of digits, such as `123`.
+pub fn parse(literal: &str) -> Option<BigInt> {
+    if literal.is_empty() || !literal.chars().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    literal.parse().ok()
+}
+
+pub fn to_f64(value: &BigInt) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// Applies an arithmetic operator to two integers when the result is an
+/// integer: `/` only when it divides exactly, `^^` only for small enough
+/// non-negative powers. `//` and `%` round the quotient down, so `a % b` has
+/// the sign of `b`.
+pub fn apply(operator: &str, a: &BigInt, b: &BigInt) -> Option<BigInt> {
+    match operator {
+        "+" => Some(a + b),
+        "-" => Some(a - b),
+        "*" => Some(a * b),
+        "/" if !b.is_zero() && a.is_multiple_of(b) => Some(a / b),
+        "//" if !b.is_zero() => Some(a.div_floor(b)),
+        "%" if !b.is_zero() => Some(a.mod_floor(b)),
+        "^^" => power(a, b),
+        _ => None,
+    }
+}
+
+fn power(base: &BigInt, exponent: &BigInt) -> Option<BigInt> {
+    let exponent = exponent.to_u32()?;
+    if base.magnitude().bits().saturating_mul(exponent as u64) > MAX_BITS {
+        return None;
+    }
+    Some(Pow::pow(base, exponent))
+}
+
+pub fn factorial(n: &BigInt) -> Option<BigInt> {
+    let n = n.to_u32().filter(|n| *n <= MAX_FACTORIAL)?;
+    Some((2..=n).fold(BigInt::one(), |product, factor| product * factor))
+}
+
+pub fn is_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for witness in WITNESSES {
+        let witness = BigInt::from(witness);
+        if *n == witness {
+            return true;
+        }
+        if n.is_multiple_of(&witness) {
+            return false;
+        }
+    }
+
+    // n - 1 = d * 2 ^ s with d odd.
+    let one = BigInt::one();
+    let n_minus_one = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+    WITNESSES.iter().all(|witness| {
+        let mut x = BigInt::from(*witness).modpow(&d, n);
+        if x == one || x == n_minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = x.modpow(&BigInt::from(2), n);
+            if x == n_minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Names of the integer built-ins.
+pub const FUNCTIONS: [&str; 5] = ["gcd", "lcm", "isprime", "modpow", "factorial"];
+
+/// Evaluates an integer built-in, or `None` if the name, argument count or
+/// arguments do not fit.
+pub fn apply_function(name: &str, arguments: &[BigInt]) -> Option<BigInt> {
+    match (name, arguments) {
+        ("gcd", [first, rest @ ..]) => Some(rest.iter().fold(first.abs(), |gcd, n| gcd.gcd(n))),
+        ("lcm", [first, rest @ ..]) => Some(rest.iter().fold(first.abs(), |lcm, n| lcm.lcm(n))),
+        ("isprime", [n]) => Some(BigInt::from(is_prime(n) as u8)),
+        ("modpow", [base, exponent, modulus]) if !exponent.is_negative() && !modulus.is_zero() => {
+            Some(base.modpow(exponent, modulus))
+        }
+        ("factorial", [n]) => factorial(n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(literal: &str) -> BigInt {
+        parse(literal).unwrap()
+    }
+
+    #[test]
+    fn large_results_are_exact() {
+        assert_eq!(factorial(&integer("50")), Some(integer("30414093201713378043612608166064768844377641568960512000000000000")));
+        assert_eq!(apply("^^", &integer("2"), &integer("200")), Some(integer("1606938044258990275541962092341162602522202993782792835301376")));
+    }
+
+    #[test]
+    fn division_and_remainder_round_down() {
+        let seven = integer("7");
+        let minus_two = -integer("2");
+        assert_eq!(apply("//", &seven, &minus_two), Some(-integer("4")));
+        assert_eq!(apply("%", &seven, &minus_two), Some(-integer("1")));
+        assert_eq!(apply("/", &seven, &minus_two), None);
+        assert_eq!(apply("%", &seven, &integer("0")), None);
+    }
+
+    #[test]
+    fn built_ins() {
+        assert_eq!(apply_function("gcd", &[integer("12"), integer("18")]), Some(integer("6")));
+        assert_eq!(apply_function("lcm", &[integer("4"), integer("6")]), Some(integer("12")));
+        assert_eq!(apply_function("modpow", &[integer("3"), integer("200"), integer("7")]), Some(integer("2")));
+        assert!(is_prime(&integer("1000000007")));
+        assert!(!is_prime(&integer("561")));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::operators::OperatorTable;
+use crate::symbols::{greek_letter, latex_symbol};
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::Associativity::{Left, Right};
+use crate::tokens::TokenType::{Function, Operator, VariableName};
+
+/// Functions LaTeX typesets upright with a command of the same name.
+const NAMED_FUNCTIONS: [&str; 21] = [
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "coth", "ln", "log", "lg", "exp", "min", "max", "gcd", "det",
+];
+
+/// Renders the tree as LaTeX math-mode source, e.g. `\frac{a}{b^{2}}` for `a / b ^^ 2`,
+/// placing parentheses by the precedence in `operators`.
+pub fn to_latex(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let name = tree.value_as_string();
+    let children = tree.children();
+    match tree.token_type() {
+        Operator if children.len() == 2 => {
+            let (left, right) = (&children[0], &children[1]);
+            match name.as_str() {
+                "/" => format!("\\frac{{{}}}{{{}}}", to_latex(left, operators), to_latex(right, operators)),
+                "^^" => {
+                    // A fraction is only self-delimiting until it gets an exponent attached.
+                    let base = if left.token_type() == Operator && left.value_as_string() == "/" {
+                        format!("\\left({}\\right)", to_latex(left, operators))
+                    } else {
+                        operand(left, operators, precedence(tree, operators), true)
+                    };
+                    format!("{}^{{{}}}", base, to_latex(right, operators))
+                }
+                _ => {
+                    let associativity = operators.associativity(name.as_str());
+                    format!("{} {} {}",
+                            operand(left, operators, precedence(tree, operators), associativity != Left),
+                            latex_symbol(name.as_str()),
+                            operand(right, operators, precedence(tree, operators), associativity != Right))
+                }
+            }
+        }
+
+        Operator if children.len() == 1 => {
+            let argument = operand(&children[0], operators, precedence(tree, operators), true);
+            match name.as_str() {
+                "NEGATE" => format!("-{}", argument),
+                "NOT" => format!("\\lnot {}", argument),
+                "COMPLEMENT" => format!("\\sim {}", argument),
+                "!" => format!("{}!", argument),
+                _ => format!("\\mathrm{{{}}}\\ {}", name, argument),
+            }
+        }
+
+        Function => {
+            let arguments: Vec<String> = children.iter().map(|child| to_latex(child, operators)).collect();
+            match name.as_str() {
+                "sqrt" if children.len() == 1 => format!("\\sqrt{{{}}}", arguments[0]),
+                "abs" if children.len() == 1 => format!("\\left|{}\\right|", arguments[0]),
+                "vector" => format!("\\left[{}\\right]", arguments.join(", ")),
+                _ => format!("{}\\left({}\\right)", function_name(name.as_str()), arguments.join(", ")),
+            }
+        }
+
+        VariableName => identifier(name.as_str()),
+
+        _ => name,
+    }
+}
+
+/// Fractions are delimited by their own typesetting, so they never need
+/// parentheses around them.
+fn precedence(tree: &SyntaxTreeNode, operators: &OperatorTable) -> isize {
+    if tree.token_type() == Operator && tree.value_as_string() == "/" {
+        return isize::MAX;
+    }
+    tree.precedence(operators)
+}
+
+fn operand(child: &SyntaxTreeNode, operators: &OperatorTable, parent_precedence: isize, tie_needs_parentheses: bool) -> String {
+    let child_precedence = precedence(child, operators);
+    if child_precedence < parent_precedence || (child_precedence == parent_precedence && tie_needs_parentheses) {
+        format!("\\left({}\\right)", to_latex(child, operators))
+    } else {
+        to_latex(child, operators)
+    }
+}
+
+fn function_name(name: &str) -> String {
+    if NAMED_FUNCTIONS.contains(&name) {
+        format!("\\{}", name)
+    } else {
+        format!("\\operatorname{{{}}}", name)
+    }
+}
+
+/// Typesets `a_b` as `a` subscripted with `b`. Underscores after the first are
+/// escaped, as in `\mathrm{speed}_{\mathrm{of\_light}}`.
+fn identifier(name: &str) -> String {
+    match name.split_once('_') {
+        Some((base, subscript)) if !base.is_empty() && !subscript.is_empty() => {
+            format!("{}_{{{}}}", word(base), word(subscript))
+        }
+        _ => word(name),
+    }
+}
+
+fn word(name: &str) -> String {
+    if greek_letter(name).is_some() {
+        format!("\\{}", name)
+    } else if name.chars().count() > 1 {
+        format!("\\mathrm{{{}}}", name.replace('_', "\\_"))
+    } else {
+        String::from(name)
+    }
+}
@@ -1,17 +1,66 @@
-use crate::tokens::{string_to_rpn, Token, tokenize};
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::decimal::Rounding;
+use crate::notation::Notation;
+use crate::operators::OperatorTable;
+use crate::settings::{ImplicitMultiplication, NumberMode, Settings};
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::{string_to_rpn, Token};
+use crate::tokens::TokenType::VariableName;
+use crate::tokens::TokenUnion::{DecimalOf, FloatOf, RationalOf};
 
 mod tokens;
 mod solver;
 mod operators;
 mod syntaxtree;
+mod notation;
+mod latex;
+mod pretty;
+mod symbols;
+mod derivative;
+mod simplify;
+mod optimizer;
+mod numeric;
+mod equation;
+mod polynomial;
+mod settings;
+mod rational;
+mod decimal;
+mod integer;
+mod complex;
+mod units;
+mod matrix;
 
 fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    match arguments.first().map(String::as_str) {
+        Some("convert") => convert(&arguments[1..]),
+        Some("latex") => render(&arguments[1..], latex::to_latex),
+        Some("unicode") => render(&arguments[1..], pretty::to_unicode),
+        Some("mathml") => render(&arguments[1..], pretty::to_mathml),
+        Some("simplify") => simplify(&arguments[1..]),
+        Some("diff") => diff(&arguments[1..]),
+        Some("optimize") => optimize(&arguments[1..]),
+        Some("solve") => solve(&arguments[1..]),
+        Some("eval") => eval(&arguments[1..]),
+        Some("calc") => calc(&arguments[1..]),
+        Some("expand") => expand(&arguments[1..]),
+        Some("collect") => collect(&arguments[1..]),
+        Some("factor") => factor(&arguments[1..]),
+        Some("divide") => divide(&arguments[1..]),
+        Some("gcd") => gcd(&arguments[1..]),
+        Some("lexbench") => lexbench(&arguments[1..]),
+        _ => demo(),
+    }
+}
+
+fn demo() {
     let string = "2 ^^ 2 + 3 - 4";
-    let vf = tokenize!(string);
     let rpn = string_to_rpn!(string);
     if let Ok(rpn) = rpn {
         let token = solver::solve_reverse_polish_notation(&rpn);
-        println!("\n{}\n\n", Token::tokens_to_string(&rpn));
+        println!("\n{}\n", Token::tokens_to_string(&rpn));
+        println!("{}\n\n", syntaxtree::polish_notation_to_normal(&rpn, OperatorTable::builtin()));
         let tree = syntaxtree::reverse_polish_string_to_tree(&rpn);
         for tr in tree {
             tr.print();
@@ -25,3 +74,331 @@ fn main() {
         println!("Error. Bad expression.")
     }
 }
+
+/// `convert <from> <to> <expression>`, where the notations are `infix`, `prefix`, `rpn` or `sexpr`.
+fn convert(arguments: &[String]) {
+    if arguments.len() != 3 {
+        println!("Usage: convert <infix|prefix|rpn|sexpr> <infix|prefix|rpn|sexpr> <expression>");
+        return;
+    }
+    let from = Notation::from_name(arguments[0].as_str());
+    let to = Notation::from_name(arguments[1].as_str());
+    if let (Some(from), Some(to)) = (from, to) {
+        match notation::convert(arguments[2].as_str(), from, to, OperatorTable::builtin()) {
+            Ok(converted) => println!("{}", converted),
+            Err(message) => println!("{}", message),
+        }
+    } else {
+        println!("Error. Unknown notation.");
+    }
+}
+
+/// `latex <expression>`, `unicode <expression>` and `mathml <expression>`.
+fn render(arguments: &[String], renderer: fn(&SyntaxTreeNode, &OperatorTable) -> String) {
+    if arguments.len() != 1 {
+        println!("Usage: <latex|unicode|mathml> <expression>");
+        return;
+    }
+    match syntaxtree::string_to_tree(arguments[0].as_str()) {
+        Ok(tree) => println!("{}", renderer(&tree, OperatorTable::builtin())),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `simplify <expression> [rule,rule,...]`, using every default rule when none are named.
+fn simplify(arguments: &[String]) {
+    if arguments.is_empty() || arguments.len() > 2 {
+        println!("Usage: simplify <expression> [rule,rule,...]");
+        return;
+    }
+    let rules = match arguments.get(1) {
+        Some(names) => simplify::rules_named(&names.split(',').collect::<Vec<&str>>()),
+        None => Ok(simplify::default_rules()),
+    };
+    let simplified = rules.and_then(|rules| {
+        let tree = syntaxtree::string_to_tree(arguments[0].as_str())?;
+        Ok(simplify::simplify_with(&tree, &rules))
+    });
+    match simplified {
+        Ok(simplified) => println!("{}", simplified),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `diff <expression> <variable>`
+fn diff(arguments: &[String]) {
+    if arguments.len() != 2 {
+        println!("Usage: diff <expression> <variable>");
+        return;
+    }
+    let derivative = syntaxtree::string_to_tree(arguments[0].as_str()).and_then(|tree| {
+        let variable = syntaxtree::string_to_tree(arguments[1].as_str())?;
+        if variable.token_type() != VariableName {
+            return Err(format!("Error. Can only differentiate with respect to a variable, not {}.", arguments[1]));
+        }
+        if solver::constant(variable.value_as_string().as_str()).is_some() {
+            return Err(format!("Error. Cannot differentiate with respect to the constant {}.", arguments[1]));
+        }
+        derivative::derive(&tree, variable.value_as_string().as_str())
+    });
+    match derivative {
+        Ok(derivative) => println!("{}", simplify::simplify(&derivative)),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `optimize <expression>`, printing the folded RPN followed by what was folded or dropped.
+fn optimize(arguments: &[String]) {
+    if arguments.len() != 1 {
+        println!("Usage: optimize <expression>");
+        return;
+    }
+    match string_to_rpn!(arguments[0].as_str()) {
+        Ok(rpn) => {
+            let optimized = optimizer::optimize(&rpn);
+            println!("{}", Token::tokens_to_string(&optimized.rpn));
+            for folding in &optimized.folded {
+                println!("folded {} => {}", folding.expression, folding.value.value_as_string());
+            }
+            for expression in &optimized.eliminated {
+                println!("eliminated {}", expression);
+            }
+        }
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `solve <equation> [unknown]`
+fn solve(arguments: &[String]) {
+    if arguments.is_empty() || arguments.len() > 2 {
+        println!("Usage: solve <equation> [unknown]");
+        return;
+    }
+    let solution = syntaxtree::string_to_tree(arguments[0].as_str())
+        .and_then(|equation| equation::solve_equation(&equation, arguments.get(1).map(String::as_str)));
+    match solution {
+        Ok(solution) => println!("{}", solution),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `eval <expression> [name=value ...]`
+fn eval(arguments: &[String]) {
+    if arguments.is_empty() {
+        println!("Usage: eval <expression> [name=value ...]");
+        return;
+    }
+    let mut bindings = HashMap::new();
+    for binding in &arguments[1..] {
+        match binding.split_once('=').map(|(name, value)| (name, value.parse::<f64>())) {
+            Some((name, Ok(value))) => {
+                bindings.insert(String::from(name), value);
+            }
+            _ => {
+                println!("Error. Bad binding {}.", binding);
+                return;
+            }
+        }
+    }
+    let value = syntaxtree::string_to_tree(arguments[0].as_str())
+        .and_then(|tree| solver::evaluate_tree(&tree, &bindings));
+    match value {
+        Ok(value) => println!("{}", value),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `calc [options] <expression>`, evaluating in the number mode the options select.
+/// `--digits` prints the result with that many digits after the point, and
+/// `--complex` reads `i` and `j` as the imaginary unit, `--units` reads
+/// unit symbols such as `km` as quantities, `--chain` lets comparisons chain
+/// as in `1 < x <= 10`, and `--implicit` multiplies adjacent operands as in `2x`,
+/// binding tighter than `/` with `--implicit=tight`.
+fn calc(arguments: &[String]) {
+    let usage = "Usage: calc [--rational | --decimal [--precision=<n>] [--rounding=half-even|half-up|down]] [--complex] [--units] [--chain] [--implicit[=tight]] [--digits=<n>] <expression>";
+    let mut settings = Settings::default();
+    let mut digits = None;
+    let mut expression = None;
+    for argument in arguments {
+        let option = argument.split_once('=').unwrap_or((argument.as_str(), ""));
+        let parsed = match option {
+            ("--rational", "") => {
+                settings.number_mode = NumberMode::Rational;
+                Ok(())
+            }
+            ("--decimal", "") => {
+                settings.number_mode = NumberMode::Decimal;
+                Ok(())
+            }
+            ("--complex", "") => {
+                settings.complex = true;
+                Ok(())
+            }
+            ("--units", "") => {
+                settings.units = true;
+                Ok(())
+            }
+            ("--chain", "") => {
+                settings.chained_comparisons = true;
+                Ok(())
+            }
+            ("--implicit", "") => {
+                settings.implicit_multiplication = ImplicitMultiplication::SameAsProduct;
+                Ok(())
+            }
+            ("--implicit", "tight") => {
+                settings.implicit_multiplication = ImplicitMultiplication::Tighter;
+                Ok(())
+            }
+            ("--precision", value) => value.parse::<u32>().ok().filter(|precision| *precision > 0)
+                .map(|precision| settings.decimal.precision = precision)
+                .ok_or_else(|| format!("Error. Bad precision {}.", value)),
+            ("--rounding", value) => Rounding::from_name(value)
+                .map(|rounding| settings.decimal.rounding = rounding)
+                .ok_or_else(|| format!("Error. Unknown rounding mode {}.", value)),
+            ("--digits", value) => value.parse::<u32>().ok()
+                .map(|value| digits = Some(value))
+                .ok_or_else(|| format!("Error. Bad digit count {}.", value)),
+            _ if expression.is_none() && !argument.starts_with("--") => {
+                expression = Some(argument.as_str());
+                Ok(())
+            }
+            _ => Err(String::from(usage)),
+        };
+        if let Err(message) = parsed {
+            println!("{}", message);
+            return;
+        }
+    }
+    let expression = match expression {
+        Some(expression) => expression,
+        None => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    if settings.number_mode == NumberMode::Decimal && (settings.complex || settings.units) {
+        println!("Error. --decimal cannot be combined with --complex or --units.");
+        return;
+    }
+
+    match string_to_rpn!(expression, &settings) {
+        Ok(rpn) => match solver::solve_reverse_polish_notation_with(&rpn, &settings) {
+            Ok(token) => match (&token.value, digits) {
+                (RationalOf(r), Some(digits)) => println!("{}", rational::to_decimal(r, digits as usize)),
+                (DecimalOf(d), Some(digits)) => println!("{}", d.to_places(digits, settings.decimal.rounding)),
+                (FloatOf(f), Some(digits)) => println!("{:.*}", digits as usize, f),
+                _ => println!("{}", token.value_as_string()),
+            },
+            Err(message) => println!("{}", message),
+        },
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `lexbench [<kilobytes>]`, timing the tokenizer on a generated expression of
+/// about that size, 1024 by default.
+fn lexbench(arguments: &[String]) {
+    let kilobytes = match arguments {
+        [] => 1024,
+        [size] => match size.parse::<usize>() {
+            Ok(size) if size > 0 => size,
+            _ => {
+                println!("Error. Bad size {}.", size);
+                return;
+            }
+        },
+        _ => {
+            println!("Usage: lexbench [<kilobytes>]");
+            return;
+        }
+    };
+    let term = "sin(x) * 2.5 + max(y, 3) ^^ 2 - ";
+    let mut input = term.repeat(kilobytes * 1024 / term.len() + 1);
+    input.push('1');
+
+    let start = Instant::now();
+    let tokens = match Token::tokenize_string(input.as_str()) {
+        Ok(tokens) => tokens,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+    let seconds = start.elapsed().as_secs_f64();
+    println!("{} tokens from {} bytes in {:.1} ms ({:.1} MB/s)",
+             tokens.len(), input.len(), seconds * 1000.0, input.len() as f64 / 1e6 / seconds);
+}
+
+/// `expand <expression>`
+fn expand(arguments: &[String]) {
+    if arguments.len() != 1 {
+        println!("Usage: expand <expression>");
+        return;
+    }
+    match syntaxtree::string_to_tree(arguments[0].as_str()).and_then(|tree| polynomial::expand(&tree)) {
+        Ok(expanded) => println!("{}", expanded),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `collect <expression> <variable>`
+fn collect(arguments: &[String]) {
+    if arguments.len() != 2 {
+        println!("Usage: collect <expression> <variable>");
+        return;
+    }
+    match syntaxtree::string_to_tree(arguments[0].as_str()).and_then(|tree| polynomial::collect(&tree, arguments[1].as_str())) {
+        Ok(collected) => println!("{}", collected),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `factor <polynomial>`
+fn factor(arguments: &[String]) {
+    if arguments.len() != 1 {
+        println!("Usage: factor <polynomial>");
+        return;
+    }
+    let factored = syntaxtree::string_to_tree(arguments[0].as_str())
+        .and_then(|tree| polynomial::factor(&tree));
+    match factored {
+        Ok(factored) => println!("{}", factored),
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `divide <dividend> <divisor>`, printing the quotient and the remainder.
+fn divide(arguments: &[String]) {
+    if arguments.len() != 2 {
+        println!("Usage: divide <dividend> <divisor>");
+        return;
+    }
+    let division = syntaxtree::string_to_tree(arguments[0].as_str()).and_then(|dividend| {
+        let divisor = syntaxtree::string_to_tree(arguments[1].as_str())?;
+        polynomial::divide(&dividend, &divisor)
+    });
+    match division {
+        Ok((quotient, remainder)) => {
+            println!("quotient {}", quotient);
+            println!("remainder {}", remainder);
+        }
+        Err(message) => println!("{}", message),
+    }
+}
+
+/// `gcd <polynomial> <polynomial>`
+fn gcd(arguments: &[String]) {
+    if arguments.len() != 2 {
+        println!("Usage: gcd <polynomial> <polynomial>");
+        return;
+    }
+    let divisor = syntaxtree::string_to_tree(arguments[0].as_str()).and_then(|a| {
+        let b = syntaxtree::string_to_tree(arguments[1].as_str())?;
+        polynomial::gcd(&a, &b)
+    });
+    match divisor {
+        Ok(divisor) => println!("{}", divisor),
+        Err(message) => println!("{}", message),
+    }
+}
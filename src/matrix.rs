@@ -0,0 +1,242 @@
+/// Linear-algebra built-ins, besides the `vector` built-in that `[...]` literals
+/// are parsed into.
+pub const FUNCTIONS: [&str; 6] = ["transpose", "det", "inv", "dot", "cross", "norm"];
+
+/// Pivots smaller than this count as zero when eliminating.
+const SINGULAR_TOLERANCE: f64 = 1e-12;
+
+/// Operand or result of a linear-algebra operation. Matrices are stored row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Scalar(f64),
+    Vector(Vec<f64>),
+    Matrix(Vec<Vec<f64>>),
+}
+
+use Value::{Matrix, Scalar, Vector};
+
+impl Value {
+    /// Names the shape for error messages, e.g. `a 2x3 matrix`.
+    fn shape(&self) -> String {
+        match self {
+            Scalar(_) => String::from("a number"),
+            Vector(v) => format!("a vector of length {}", v.len()),
+            Matrix(m) if is_empty(m) => String::from("an empty matrix"),
+            Matrix(m) => format!("a {}x{} matrix", m.len(), m[0].len()),
+        }
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64 + Copy) -> Value {
+        match self {
+            Scalar(x) => Scalar(f(*x)),
+            Vector(v) => Vector(v.iter().map(|x| f(*x)).collect()),
+            Matrix(m) => Matrix(m.iter().map(|row| row.iter().map(|x| f(*x)).collect()).collect()),
+        }
+    }
+}
+
+/// Writes a vector as `[1, 2]` and a matrix as `[[1, 2], [3, 4]]`.
+pub fn format_vector(v: &[f64]) -> String {
+    format!("[{}]", v.iter().map(f64::to_string).collect::<Vec<String>>().join(", "))
+}
+
+pub fn format_matrix(m: &[Vec<f64>]) -> String {
+    format!("[{}]", m.iter().map(|row| format_vector(row)).collect::<Vec<String>>().join(", "))
+}
+
+/// Builds the value of a `[...]` literal: numbers make a vector, vectors of the
+/// same length make a matrix with them as rows.
+pub fn from_elements(elements: &[Value]) -> Result<Value, String> {
+    if let Some(numbers) = elements.iter().map(|e| if let Scalar(x) = e { Some(*x) } else { None }).collect::<Option<Vec<f64>>>() {
+        return Ok(Vector(numbers));
+    }
+    let rows: Option<Vec<Vec<f64>>> = elements.iter().map(|e| if let Vector(v) = e { Some(v.clone()) } else { None }).collect();
+    match rows {
+        Some(rows) if rows.iter().all(|row| !row.is_empty() && row.len() == rows[0].len()) => Ok(Matrix(rows)),
+        Some(_) => Err(String::from("Error. Rows of a matrix must be non-empty and of the same length.")),
+        None => Err(String::from("Error. Elements of a vector must be numbers, and rows of a matrix vectors.")),
+    }
+}
+
+/// A matrix without rows or columns, which none of the operations accept.
+fn is_empty(m: &[Vec<f64>]) -> bool {
+    m.first().is_none_or(Vec::is_empty)
+}
+
+fn check_not_empty(values: &[&Value]) -> Result<(), String> {
+    if values.iter().any(|value| matches!(value, Matrix(m) if is_empty(m))) {
+        return Err(String::from("Error. The matrix is empty."));
+    }
+    Ok(())
+}
+
+fn mismatch(action: &str, a: &Value, b: &Value) -> String {
+    format!("Error. Cannot {} {} and {}.", action, a.shape(), b.shape())
+}
+
+fn transpose(m: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    (0..m[0].len()).map(|j| m.iter().map(|row| row[j]).collect()).collect()
+}
+
+fn product(a: &[Vec<f64>], b: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let columns = transpose(b);
+    a.iter().map(|row| columns.iter().map(|column| dot(row, column)).collect()).collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn identity(size: usize) -> Vec<Vec<f64>> {
+    (0..size).map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+fn square(m: &[Vec<f64>], name: &str) -> Result<(), String> {
+    if is_empty(m) {
+        return Err(String::from("Error. The matrix is empty."));
+    }
+    if m.len() != m[0].len() {
+        return Err(format!("Error. {} needs a square matrix, not {}.", name, Matrix(m.to_vec()).shape()));
+    }
+    Ok(())
+}
+
+/// Determinant by Gaussian elimination with partial pivoting.
+fn determinant(m: &[Vec<f64>]) -> f64 {
+    let mut m = m.to_vec();
+    let size = m.len();
+    let mut determinant = 1.0;
+    for column in 0..size {
+        let pivot = (column..size).max_by(|&i, &j| m[i][column].abs().total_cmp(&m[j][column].abs())).unwrap();
+        if m[pivot][column] == 0.0 {
+            return 0.0;
+        }
+        if pivot != column {
+            m.swap(pivot, column);
+            determinant = -determinant;
+        }
+        determinant *= m[column][column];
+        let (done, rest) = m.split_at_mut(column + 1);
+        let pivot_row = &done[column];
+        for row in rest {
+            let factor = row[column] / pivot_row[column];
+            for (element, pivot_element) in row.iter_mut().zip(pivot_row).skip(column) {
+                *element -= factor * pivot_element;
+            }
+        }
+    }
+    determinant
+}
+
+/// Inverse by Gauss–Jordan elimination with partial pivoting.
+fn inverse(m: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+    let size = m.len();
+    let mut left = m.to_vec();
+    let mut right = identity(size);
+    for column in 0..size {
+        let pivot = (column..size).max_by(|&i, &j| left[i][column].abs().total_cmp(&left[j][column].abs())).unwrap();
+        if left[pivot][column].abs() < SINGULAR_TOLERANCE {
+            return Err(String::from("Error. The matrix is singular."));
+        }
+        left.swap(pivot, column);
+        right.swap(pivot, column);
+        let scale = left[column][column];
+        for k in 0..size {
+            left[column][k] /= scale;
+            right[column][k] /= scale;
+        }
+        for row in 0..size {
+            if row != column {
+                let factor = left[row][column];
+                for k in 0..size {
+                    left[row][k] -= factor * left[column][k];
+                    right[row][k] -= factor * right[column][k];
+                }
+            }
+        }
+    }
+    Ok(right)
+}
+
+fn power(m: &[Vec<f64>], exponent: f64) -> Result<Vec<Vec<f64>>, String> {
+    square(m, "^^")?;
+    if exponent.fract() != 0.0 {
+        return Err(String::from("Error. Matrices can only be raised to integer powers."));
+    }
+    let mut base = if exponent < 0.0 { inverse(m)? } else { m.to_vec() };
+    let mut exponent = exponent.abs() as u64;
+    let mut result = identity(m.len());
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            result = product(&result, &base);
+        }
+        base = product(&base, &base);
+        exponent /= 2;
+    }
+    Ok(result)
+}
+
+fn element_wise(a: &Value, b: &Value, action: &str, f: impl Fn(f64, f64) -> f64 + Copy) -> Result<Value, String> {
+    match (a, b) {
+        (Scalar(x), _) => Ok(b.map(|y| f(*x, y))),
+        (_, Scalar(y)) => Ok(a.map(|x| f(x, *y))),
+        (Vector(u), Vector(v)) if u.len() == v.len() => Ok(Vector(u.iter().zip(v).map(|(x, y)| f(*x, *y)).collect())),
+        (Matrix(m), Matrix(n)) if m.len() == n.len() && m[0].len() == n[0].len() => Ok(Matrix(m.iter().zip(n)
+            .map(|(r, s)| r.iter().zip(s).map(|(x, y)| f(*x, *y)).collect())
+            .collect())),
+        _ => Err(mismatch(action, a, b)),
+    }
+}
+
+pub fn negate(value: &Value) -> Value {
+    value.map(|x| -x)
+}
+
+/// Applies an arithmetic operator where at least one operand is a vector or a
+/// matrix. `+`, `-` and `/` work element by element, as does `*` with a number or
+/// between two vectors; `*` with a matrix is the matrix product, treating a
+/// vector on the left as a row and on the right as a column.
+pub fn apply(operator: &str, a: &Value, b: &Value) -> Result<Value, String> {
+    check_not_empty(&[a, b])?;
+    match (operator, a, b) {
+        ("+", _, _) => element_wise(a, b, "add", |x, y| x + y),
+        ("-", _, _) => element_wise(a, b, "subtract", |x, y| x - y),
+        ("/", _, Scalar(_)) => element_wise(a, b, "divide", |x, y| x / y),
+        ("/", Vector(_), Vector(_)) => element_wise(a, b, "divide", |x, y| x / y),
+        ("*", Matrix(m), Matrix(n)) if m[0].len() == n.len() => Ok(Matrix(product(m, n))),
+        ("*", Matrix(m), Vector(v)) if m[0].len() == v.len() => Ok(Vector(m.iter().map(|row| dot(row, v)).collect())),
+        ("*", Vector(v), Matrix(m)) if v.len() == m.len() => Ok(Vector(transpose(m).iter().map(|column| dot(v, column)).collect())),
+        ("*", Matrix(_), _) | ("*", _, Matrix(_)) if !matches!((a, b), (Scalar(_), _) | (_, Scalar(_))) => Err(mismatch("multiply", a, b)),
+        ("*", _, _) => element_wise(a, b, "multiply", |x, y| x * y),
+        ("^^", Matrix(m), Scalar(exponent)) => power(m, *exponent).map(Matrix),
+        ("^^", Vector(_), Scalar(_)) => element_wise(a, b, "raise", f64::powf),
+        _ => Err(format!("Error. Cannot apply {} to {} and {}.", operator, a.shape(), b.shape())),
+    }
+}
+
+/// Evaluates a linear-algebra built-in.
+pub fn apply_function(name: &str, arguments: &[Value]) -> Result<Value, String> {
+    check_not_empty(&arguments.iter().collect::<Vec<&Value>>())?;
+    match (name, arguments) {
+        ("transpose", [Vector(v)]) if v.is_empty() => Err(String::from("Error. Cannot transpose an empty vector.")),
+        ("transpose", [Vector(v)]) => Ok(Matrix(v.iter().map(|x| vec![*x]).collect())),
+        ("transpose", [Matrix(m)]) => Ok(Matrix(transpose(m))),
+        ("det", [Matrix(m)]) => square(m, "det").map(|_| Scalar(determinant(m))),
+        ("inv", [Matrix(m)]) => square(m, "inv").and_then(|_| inverse(m)).map(Matrix),
+        ("dot", [Vector(u), Vector(v)]) if u.len() == v.len() => Ok(Scalar(dot(u, v))),
+        ("cross", [Vector(u), Vector(v)]) if u.len() == 3 && v.len() == 3 => Ok(Vector(vec![
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ])),
+        ("norm", [Vector(v)]) => Ok(Scalar(dot(v, v).sqrt())),
+        ("norm", [Matrix(m)]) => Ok(Scalar(m.iter().map(|row| dot(row, row)).sum::<f64>().sqrt())),
+        ("dot", [a, b]) => Err(mismatch("take the dot product of", a, b)),
+        ("cross", [a, b]) => Err(mismatch("take the cross product of", a, b)),
+        _ => Err(format!(
+            "Error. {} does not take {}.",
+            name,
+            arguments.iter().map(Value::shape).collect::<Vec<String>>().join(" and "),
+        )),
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashSet;
+
+use crate::integer;
+use crate::operators::{chain_links, chain_name, OperatorTable};
+use crate::settings::Settings;
+use crate::syntaxtree::{string_to_tree_with, SyntaxTreeNode};
+use crate::tokens::Associativity::{NoAsoc, Right};
+use crate::tokens::TokenType::{Function, Numeric, Operator};
+use crate::tokens::TokenUnion::IntegerOf;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Copy, Clone)]
+pub enum Notation {
+    /// `2 + 3 * 4`
+    Infix,
+    /// `+ 2 * 3 4`
+    Prefix,
+    /// `2 3 4 * +`
+    Postfix,
+    /// `(+ 2 (* 3 4))`
+    SExpression,
+}
+
+impl Notation {
+    pub fn from_name(name: &str) -> Option<Notation> {
+        match name {
+            "infix" => Some(Notation::Infix),
+            "prefix" | "polish" => Some(Notation::Prefix),
+            "postfix" | "rpn" => Some(Notation::Postfix),
+            "sexpr" | "lisp" => Some(Notation::SExpression),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `string`, reading operators from `operators`.
+pub fn parse(notation: Notation, string: &str, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    match notation {
+        Notation::Infix => string_to_tree_with(string, &Settings { operators: operators.clone(), ..Settings::default() }),
+        Notation::Prefix => parse_prefix(string, operators),
+        Notation::Postfix => parse_postfix(string, operators),
+        Notation::SExpression => parse_s_expression(string, operators),
+    }
+}
+
+pub fn print(notation: Notation, tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    match notation {
+        Notation::Infix => tree.display_with(operators).to_string(),
+        Notation::Prefix => to_prefix(tree, operators),
+        Notation::Postfix => to_postfix(tree, operators),
+        Notation::SExpression => to_s_expression(tree, operators),
+    }
+}
+
+pub fn convert(string: &str, from: Notation, to: Notation, operators: &OperatorTable) -> Result<String, String> {
+    Ok(print(to, &parse(from, string, operators)?, operators))
+}
+
+/// Built-in functions that prefix and postfix input may name without an arity,
+/// with the number of arguments they take when written that way.
+const FUNCTION_ARITIES: [(&str, usize); 31] = [
+    ("sin", 1), ("cos", 1), ("tan", 1), ("asin", 1), ("acos", 1), ("atan", 1), ("atan2", 2),
+    ("sinh", 1), ("cosh", 1), ("tanh", 1), ("exp", 1), ("ln", 1), ("log", 1), ("sqrt", 1),
+    ("abs", 1), ("floor", 1), ("ceil", 1), ("round", 1), ("min", 2), ("max", 2),
+    ("gcd", 2), ("lcm", 2), ("isprime", 1), ("modpow", 3), ("factorial", 1),
+    ("transpose", 1), ("det", 1), ("inv", 1), ("dot", 2), ("cross", 2), ("norm", 1),
+];
+
+fn default_arity(name: &str) -> Option<usize> {
+    FUNCTION_ARITIES.iter().find(|(function, _)| *function == name).map(|(_, arity)| *arity)
+}
+
+/// Every node a word of prefix or postfix input can stand for. A symbol shared by
+/// operators of different arity, like `-`, has a reading for each, and `-/1` picks
+/// one. Functions outside `FUNCTION_ARITIES` carry their arity as `name/arity`
+/// since, unlike operators, nothing else tells how many operands they take.
+fn readings(word: &str, operators: &OperatorTable) -> Result<Vec<SyntaxTreeNode>, String> {
+    if let Some(integer) = integer::parse(word) {
+        // As infix input reads it, so printed trees compare equal when read back.
+        return Ok(vec![SyntaxTreeNode::new(IntegerOf(integer), Numeric, vec![])]);
+    }
+    if word.chars().any(|c| c.is_ascii_digit()) {
+        if let Ok(number) = word.parse::<f64>() {
+            return Ok(vec![SyntaxTreeNode::number(number)]);
+        }
+    }
+    if let Some((name, arity)) = word.rsplit_once('/') {
+        if let Ok(arity) = arity.parse::<usize>() {
+            if is_identifier(name) {
+                return Ok(vec![function_node(name, arity)]);
+            }
+            let readings: Vec<SyntaxTreeNode> = operator_readings(name, operators).into_iter()
+                .filter(|reading| word_arity(reading, operators) == arity)
+                .collect();
+            if !readings.is_empty() {
+                return Ok(readings);
+            }
+        }
+    }
+    let readings = operator_readings(word, operators);
+    if !readings.is_empty() {
+        return Ok(readings);
+    }
+    if let Some(arity) = default_arity(word) {
+        return Ok(vec![function_node(word, arity)]);
+    }
+    if is_identifier(word) {
+        return Ok(vec![SyntaxTreeNode::variable(word)]);
+    }
+    Err(format!("Error. Unknown token '{}'.", word))
+}
+
+/// The operators `word` names or is typed as, the one named first. A chain of
+/// comparisons is written with commas, as in `<,<=`.
+fn operator_readings(word: &str, operators: &OperatorTable) -> Vec<SyntaxTreeNode> {
+    let links: Vec<&str> = word.split(',').collect();
+    if links.len() > 1 {
+        if !links.iter().all(|link| operators.get(link).is_some_and(|operator| operator.arity == 2)) {
+            return vec![];
+        }
+        let name = links[1..].iter().fold(String::from(links[0]), |chain, link| chain_name(chain.as_str(), link));
+        return vec![SyntaxTreeNode::operator(name.as_str(), vec![])];
+    }
+    let mut names = vec![];
+    if operators.get(word).is_some() {
+        names.push(word);
+    }
+    if let Some(name) = operators.prefix_named(word).filter(|name| *name != word) {
+        names.push(name);
+    }
+    names.into_iter().map(|name| SyntaxTreeNode::operator(name, vec![])).collect()
+}
+
+fn function_node(name: &str, arity: usize) -> SyntaxTreeNode {
+    SyntaxTreeNode::function(name, vec![SyntaxTreeNode::number(0.0); arity])
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.chars().next().map(char::is_alphabetic) == Some(true) && word.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Number of operands a classified word consumes.
+fn word_arity(node: &SyntaxTreeNode, operators: &OperatorTable) -> usize {
+    let name = node.value_as_string();
+    match node.token_type() {
+        Operator => chain_links(name.as_str()).map_or_else(|| operators.arity(name.as_str()), |links| links.len() + 1),
+        Function => node.children().len(),
+        _ => 0,
+    }
+}
+
+/// One reading for each word, such that applying the words in order to a stack
+/// leaves a single value. When there is no such choice, each word's first reading,
+/// for the caller to report what is missing.
+fn read_words(words: &[&str], operators: &OperatorTable) -> Result<Vec<SyntaxTreeNode>, String> {
+    let readings = words.iter().map(|word| readings(word, operators)).collect::<Result<Vec<_>, _>>()?;
+    let arities: Vec<Vec<usize>> = readings.iter()
+        .map(|readings| readings.iter().map(|reading| word_arity(reading, operators)).collect())
+        .collect();
+    let mut chosen = vec![];
+    if !choose_readings(&arities, 0, &mut chosen, &mut HashSet::new()) {
+        chosen = vec![0; words.len()];
+    }
+    Ok(readings.into_iter().zip(chosen).map(|(mut readings, index)| readings.swap_remove(index)).collect())
+}
+
+/// Extends `chosen` from the stack depth it leaves to a choice for every word,
+/// trying readings in order. `failed` remembers the positions and depths that
+/// lead nowhere.
+fn choose_readings(arities: &[Vec<usize>], depth: usize, chosen: &mut Vec<usize>, failed: &mut HashSet<(usize, usize)>) -> bool {
+    let index = chosen.len();
+    if index == arities.len() {
+        return depth == 1;
+    }
+    if failed.contains(&(index, depth)) {
+        return false;
+    }
+    for (reading, &arity) in arities[index].iter().enumerate() {
+        if arity <= depth {
+            chosen.push(reading);
+            if choose_readings(arities, depth - arity + 1, chosen, failed) {
+                return true;
+            }
+            chosen.pop();
+        }
+    }
+    failed.insert((index, depth));
+    false
+}
+
+fn with_children(node: &SyntaxTreeNode, children: Vec<SyntaxTreeNode>) -> SyntaxTreeNode {
+    SyntaxTreeNode::new(node.value().clone(), node.token_type(), children)
+}
+
+pub fn parse_prefix(string: &str, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    let words: Vec<&str> = string.split_whitespace().collect();
+    // Read right to left, prefix notation is postfix with the operands reversed.
+    let reversed: Vec<&str> = words.iter().rev().copied().collect();
+    let nodes: Vec<SyntaxTreeNode> = read_words(&reversed, operators)?.into_iter().rev().collect();
+    let mut position = 0;
+    let tree = parse_prefix_at(&nodes, &mut position, operators)?;
+    if position != words.len() {
+        return Err(format!("Error. Unexpected '{}' after the expression.", words[position]));
+    }
+    Ok(tree)
+}
+
+fn parse_prefix_at(nodes: &[SyntaxTreeNode], position: &mut usize, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    let node = nodes.get(*position).ok_or_else(|| String::from("Error. Missing operand."))?;
+    *position += 1;
+    let mut children = vec![];
+    for _ in 0..word_arity(node, operators) {
+        children.push(parse_prefix_at(nodes, position, operators)?);
+    }
+    Ok(with_children(node, children))
+}
+
+pub fn parse_postfix(string: &str, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    let words: Vec<&str> = string.split_whitespace().collect();
+    let mut stack: Vec<SyntaxTreeNode> = vec![];
+    for (word, node) in words.iter().zip(read_words(&words, operators)?) {
+        let arity = word_arity(&node, operators);
+        if stack.len() < arity {
+            return Err(format!("Error. Missing operand for {}.", word));
+        }
+        let children = stack.split_off(stack.len() - arity);
+        stack.push(with_children(&node, children));
+    }
+    if stack.len() != 1 {
+        return Err(String::from("Error. Bad expression."));
+    }
+    Ok(stack.remove(0))
+}
+
+enum SExpression {
+    Atom(String),
+    List(Vec<SExpression>),
+}
+
+pub fn parse_s_expression(string: &str, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    let spaced = string.replace('(', " ( ").replace(')', " ) ");
+    let words: Vec<&str> = spaced.split_whitespace().collect();
+    let mut position = 0;
+    let expression = read_s_expression(&words, &mut position)?;
+    if position != words.len() {
+        return Err(format!("Error. Unexpected '{}' after the expression.", words[position]));
+    }
+    s_expression_to_tree(&expression, operators)
+}
+
+fn read_s_expression(words: &[&str], position: &mut usize) -> Result<SExpression, String> {
+    let word = *words.get(*position).ok_or_else(|| String::from("Error. Missing ')'."))?;
+    *position += 1;
+    match word {
+        "(" => {
+            let mut items = vec![];
+            while words.get(*position) != Some(&")") {
+                items.push(read_s_expression(words, position)?);
+            }
+            *position += 1;
+            Ok(SExpression::List(items))
+        }
+        ")" => Err(String::from("Error. Unexpected ')'.")),
+        _ => Ok(SExpression::Atom(String::from(word))),
+    }
+}
+
+fn s_expression_to_tree(expression: &SExpression, operators: &OperatorTable) -> Result<SyntaxTreeNode, String> {
+    let items = match expression {
+        SExpression::Atom(word) => {
+            let node = readings(word, operators)?.remove(0);
+            if word_arity(&node, operators) != 0 {
+                return Err(format!("Error. '{}' must be applied inside a list.", word));
+            }
+            return Ok(node);
+        }
+        SExpression::List(items) => items,
+    };
+
+    let head = match items.first() {
+        Some(SExpression::Atom(head)) => head.as_str(),
+        _ => return Err(String::from("Error. A list must start with an operator or function name.")),
+    };
+    let mut arguments = vec![];
+    for item in &items[1..] {
+        arguments.push(s_expression_to_tree(item, operators)?);
+    }
+
+    let readings = operator_readings(head, operators);
+    if readings.is_empty() {
+        if !is_identifier(head) {
+            return Err(format!("Error. Unknown token '{}'.", head));
+        }
+        return Ok(SyntaxTreeNode::function(head, arguments));
+    }
+
+    if let Some(reading) = readings.iter().find(|reading| word_arity(reading, operators) == arguments.len()) {
+        return Ok(with_children(reading, arguments));
+    }
+    let operator = readings[0].value_as_string();
+    let operator = operator.as_str();
+    let arity = word_arity(&readings[0], operators);
+    if arity != 2 || arguments.len() < 2 {
+        return Err(format!("Error. {} takes {} operand(s), got {}.", head, arity, arguments.len()));
+    }
+
+    // `(+ a b c)` folds in the operator's own associativity.
+    let combine = |left, right| SyntaxTreeNode::operator(operator, vec![left, right]);
+    let associativity = operators.associativity(operator);
+    if associativity == NoAsoc {
+        return Err(format!("Error. {} is not associative and takes 2 operands, got {}.", head, arguments.len()));
+    }
+    if associativity == Right {
+        let mut reversed = arguments.into_iter().rev();
+        let last = reversed.next().unwrap();
+        Ok(reversed.fold(last, |right, left| combine(left, right)))
+    } else {
+        let mut ordered = arguments.into_iter();
+        let first = ordered.next().unwrap();
+        Ok(ordered.fold(first, combine))
+    }
+}
+
+/// Prints `tree` in prefix notation. Where writing negation as `-` would read back
+/// as a subtraction, as `- - a b` does, negations are written `-/1`.
+pub fn to_prefix(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let printed = prefix_words(tree, operators, false).join(" ");
+    if parse_prefix(printed.as_str(), operators).as_ref() == Ok(tree) {
+        return printed;
+    }
+    prefix_words(tree, operators, true).join(" ")
+}
+
+fn prefix_words(tree: &SyntaxTreeNode, operators: &OperatorTable, explicit: bool) -> Vec<String> {
+    let mut words = vec![word_for(tree, operators, explicit)];
+    for child in tree.children() {
+        words.extend(prefix_words(child, operators, explicit));
+    }
+    words
+}
+
+/// Prints `tree` in postfix notation, writing negations `-/1` where `-` would read
+/// back as a subtraction.
+pub fn to_postfix(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let printed = postfix_words(tree, operators, false).join(" ");
+    if parse_postfix(printed.as_str(), operators).as_ref() == Ok(tree) {
+        return printed;
+    }
+    postfix_words(tree, operators, true).join(" ")
+}
+
+fn postfix_words(tree: &SyntaxTreeNode, operators: &OperatorTable, explicit: bool) -> Vec<String> {
+    let mut words: Vec<String> = tree.children().iter().flat_map(|child| postfix_words(child, operators, explicit)).collect();
+    words.push(word_for(tree, operators, explicit));
+    words
+}
+
+pub fn to_s_expression(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let head = match tree.token_type() {
+        Operator => operator_word(tree.value_as_string().as_str(), operators, false),
+        Function => tree.value_as_string(),
+        _ => return tree.value_as_string(),
+    };
+    let mut words = vec![head];
+    for child in tree.children() {
+        words.push(to_s_expression(child, operators));
+    }
+    format!("({})", words.join(" "))
+}
+
+fn word_for(tree: &SyntaxTreeNode, operators: &OperatorTable, explicit: bool) -> String {
+    let name = tree.value_as_string();
+    match tree.token_type() {
+        Function if default_arity(name.as_str()) == Some(tree.children().len()) => name,
+        Function => format!("{}/{}", name, tree.children().len()),
+        Operator => operator_word(name.as_str(), operators, explicit),
+        _ => name,
+    }
+}
+
+/// How an operator is written outside infix: as it is typed, unless that reads as
+/// another operator of the same arity, as `!` does for `NOT`. With `explicit`, an
+/// operator that is not the first reading of its symbol carries its arity.
+fn operator_word(name: &str, operators: &OperatorTable, explicit: bool) -> String {
+    if let Some(links) = chain_links(name) {
+        return links.join(",");
+    }
+    let symbol = operators.spelling(name);
+    let arity = operators.arity(name);
+    let readings = operator_readings(symbol, operators);
+    let read_as = readings.iter().find(|reading| word_arity(reading, operators) == arity).map(SyntaxTreeNode::value_as_string);
+    if read_as.as_deref() != Some(name) {
+        return String::from(name);
+    }
+    if explicit && readings[0].value_as_string() != name {
+        return format!("{}/{}", symbol, arity);
+    }
+    String::from(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prints the tree of `source` in `notation`, checks that the text is
+    /// `expected`, and that it parses back into the same tree.
+    fn assert_round_trip(source: &str, notation: Notation, expected: &str, settings: &Settings) {
+        let tree = string_to_tree_with(source, settings).unwrap();
+        let printed = print(notation, &tree, &settings.operators);
+        assert_eq!(printed, expected, "printing {}", source);
+        assert_eq!(parse(notation, printed.as_str(), &settings.operators), Ok(tree), "reparsing {}", printed);
+    }
+
+    #[test]
+    fn operators_and_functions_round_trip() {
+        let settings = Settings::default();
+        for (source, prefix, postfix, s_expression) in [
+            ("-1", "- 1", "1 -", "(- 1)"),
+            ("a - -b", "- a - b", "a b -/1 -", "(- a (- b))"),
+            ("-(a - b)", "- - a b", "a b - -", "(- (- a b))"),
+            ("(-a) - b", "- -/1 a b", "a - b -", "(- (- a) b)"),
+            ("!a + b!", "+ NOT a ! b", "a NOT b ! +", "(+ (NOT a) (! b))"),
+            ("~a", "~ a", "a ~", "(~ a)"),
+            ("sin(x) * max(a, 2)", "* sin x max a 2", "x sin a 2 max *", "(* (sin x) (max a 2))"),
+            ("f(a, b)", "f/2 a b", "a b f/2", "(f a b)"),
+        ] {
+            assert_round_trip(source, Notation::Prefix, prefix, &settings);
+            assert_round_trip(source, Notation::Postfix, postfix, &settings);
+            assert_round_trip(source, Notation::SExpression, s_expression, &settings);
+        }
+    }
+
+    #[test]
+    fn chained_comparisons_round_trip() {
+        let settings = Settings { chained_comparisons: true, ..Settings::default() };
+        assert_round_trip("a < b <= c", Notation::Prefix, "<,<= a b c", &settings);
+        assert_round_trip("a < b <= c", Notation::Postfix, "a b c <,<=", &settings);
+        assert_round_trip("a < b <= c", Notation::SExpression, "(<,<= a b c)", &settings);
+    }
+}
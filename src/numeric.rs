@@ -0,0 +1,268 @@
+const MAX_ITERATIONS: usize = 200;
+
+/// Newton's method from `start`. Gives up when the derivative vanishes or an
+/// iterate stops being finite.
+pub fn newton(f: impl Fn(f64) -> f64, derivative: impl Fn(f64) -> f64, start: f64, tolerance: f64) -> Option<f64> {
+    let mut x = start;
+    for _ in 0..MAX_ITERATIONS {
+        let value = f(x);
+        if value == 0.0 {
+            return Some(x);
+        }
+        let slope = derivative(x);
+        if slope == 0.0 || !slope.is_finite() || !value.is_finite() {
+            return None;
+        }
+        let step = value / slope;
+        x -= step;
+        if !x.is_finite() {
+            return None;
+        }
+        if step.abs() <= tolerance * (1.0 + x.abs()) {
+            return Some(x);
+        }
+    }
+    None
+}
+
+/// Brent's method on a bracket `[lower, upper]` whose ends have opposite signs,
+/// combining bisection with secant and inverse quadratic interpolation steps.
+pub fn brent(f: impl Fn(f64) -> f64, lower: f64, upper: f64, tolerance: f64) -> Option<f64> {
+    let (mut a, mut b) = (lower, upper);
+    let (mut fa, mut fb) = (f(a), f(b));
+    if fa == 0.0 {
+        return Some(a);
+    }
+    if fb == 0.0 {
+        return Some(b);
+    }
+    if fa.is_nan() || fb.is_nan() || fa.signum() == fb.signum() {
+        return None;
+    }
+
+    let (mut c, mut fc) = (b, fb);
+    let mut d = b - a;
+    let mut e = d;
+    for _ in 0..MAX_ITERATIONS {
+        if (fb > 0.0) == (fc > 0.0) {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+
+        let tolerance_here = 2.0 * f64::EPSILON * b.abs() + 0.5 * tolerance;
+        let midpoint = 0.5 * (c - b);
+        if midpoint.abs() <= tolerance_here || fb == 0.0 {
+            return Some(b);
+        }
+
+        if e.abs() >= tolerance_here && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * midpoint * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (s * (2.0 * midpoint * q * (q - r) - (b - a) * (r - 1.0)), (q - 1.0) * (r - 1.0) * (s - 1.0))
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < (3.0 * midpoint * q - (tolerance_here * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = midpoint;
+                e = d;
+            }
+        } else {
+            d = midpoint;
+            e = d;
+        }
+
+        a = b;
+        fa = fb;
+        b += if d.abs() > tolerance_here { d } else { tolerance_here.copysign(midpoint) };
+        fb = f(b);
+    }
+    None
+}
+
+/// Searches outwards from `center` for an interval on which `f` changes sign,
+/// returning the one closest to `center`.
+pub fn find_bracket(f: impl Fn(f64) -> f64, center: f64) -> Option<(f64, f64)> {
+    const SAMPLES: usize = 400;
+    let mut radius = 1.0;
+    while radius <= 1e6 {
+        let step = 2.0 * radius / SAMPLES as f64;
+        let points: Vec<(f64, f64)> = (0..=SAMPLES)
+            .map(|index| center - radius + step * index as f64)
+            .map(|x| (x, f(x)))
+            .collect();
+        let bracket = points.windows(2)
+            .filter(|pair| pair[0].1.is_finite() && pair[1].1.is_finite())
+            .filter(|pair| pair[0].1 == 0.0 || pair[0].1.signum() != pair[1].1.signum())
+            .map(|pair| (pair[0].0, pair[1].0))
+            .min_by(|a, b| (a.0 - center).abs().total_cmp(&(b.0 - center).abs()));
+        if bracket.is_some() {
+            return bracket;
+        }
+        radius *= 10.0;
+    }
+    None
+}
+
+/// Brent's minimization on `[lower, upper]`: golden-section search sped up by
+/// parabolic interpolation. Returns where the smallest value was found, which
+/// may be an end of the interval.
+pub fn minimize(f: impl Fn(f64) -> f64, lower: f64, upper: f64, tolerance: f64) -> f64 {
+    let golden = 0.5 * (3.0 - 5f64.sqrt());
+    let (mut a, mut b) = (lower.min(upper), lower.max(upper));
+    let mut x = a + golden * (b - a);
+    let (mut w, mut v) = (x, x);
+    let mut fx = f(x);
+    let (mut fw, mut fv) = (fx, fx);
+    let (mut d, mut e): (f64, f64) = (0.0, 0.0);
+
+    for _ in 0..MAX_ITERATIONS {
+        let midpoint = 0.5 * (a + b);
+        let tolerance_here = tolerance * x.abs() + 1e-10;
+        if (x - midpoint).abs() <= 2.0 * tolerance_here - 0.5 * (b - a) {
+            break;
+        }
+
+        let mut golden_step = true;
+        if e.abs() > tolerance_here {
+            let r = (x - w) * (fx - fv);
+            let mut q = (x - v) * (fx - fw);
+            let mut p = (x - v) * q - (x - w) * r;
+            q = 2.0 * (q - r);
+            if q > 0.0 {
+                p = -p;
+            }
+            q = q.abs();
+            let previous = e;
+            e = d;
+            if p.abs() < (0.5 * q * previous).abs() && p > q * (a - x) && p < q * (b - x) {
+                d = p / q;
+                let u = x + d;
+                if u - a < 2.0 * tolerance_here || b - u < 2.0 * tolerance_here {
+                    d = tolerance_here.copysign(midpoint - x);
+                }
+                golden_step = false;
+            }
+        }
+        if golden_step {
+            e = if x >= midpoint { a - x } else { b - x };
+            d = golden * e;
+        }
+
+        let u = if d.abs() >= tolerance_here { x + d } else { x + tolerance_here.copysign(d) };
+        let fu = f(u);
+        if fu <= fx {
+            if u >= x {
+                a = x;
+            } else {
+                b = x;
+            }
+            (v, w, x) = (w, x, u);
+            (fv, fw, fx) = (fw, fx, fu);
+        } else {
+            if u < x {
+                a = u;
+            } else {
+                b = u;
+            }
+            if fu <= fw || w == x {
+                (v, w) = (w, u);
+                (fv, fw) = (fw, fu);
+            } else if fu <= fv || v == x || v == w {
+                v = u;
+                fv = fu;
+            }
+        }
+    }
+
+    [(lower, f(lower)), (upper, f(upper))].into_iter()
+        .fold((x, fx), |best, candidate| if candidate.1 < best.1 { candidate } else { best })
+        .0
+}
+
+/// Nodes of the 15-point Kronrod rule on `[-1, 1]`, largest first; the odd
+/// entries are the nodes of the embedded 7-point Gauss rule.
+const KRONROD_NODES: [f64; 8] = [
+    0.9914553711208126, 0.9491079123427585,
+    0.8648644233597691, 0.7415311855993945,
+    0.5860872354676911, 0.4058451513773972,
+    0.20778495500789848, 0.0,
+];
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224, 0.06309209262997856,
+    0.10479001032225019, 0.14065325971552592,
+    0.1690047266392679, 0.19035057806478542,
+    0.20443294007529889, 0.20948214108472782,
+];
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.1294849661688697, 0.27970539148927664,
+    0.3818300505051189, 0.4179591836734694,
+];
+
+/// The 15-point Kronrod estimate of the integral and its difference from the
+/// 7-point Gauss estimate, used as the error.
+fn gauss_kronrod(f: &impl Fn(f64) -> f64, a: f64, b: f64) -> (f64, f64) {
+    let center = 0.5 * (a + b);
+    let half_length = 0.5 * (b - a);
+    let (mut kronrod, mut gauss) = (0.0, 0.0);
+    for (index, node) in KRONROD_NODES.iter().enumerate() {
+        let value = if *node == 0.0 {
+            f(center)
+        } else {
+            f(center - half_length * node) + f(center + half_length * node)
+        };
+        kronrod += KRONROD_WEIGHTS[index] * value;
+        if index % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[index / 2] * value;
+        }
+    }
+    (kronrod * half_length, ((kronrod - gauss) * half_length).abs())
+}
+
+/// Adaptive Gauss–Kronrod (7/15 point) integration of `f` from `a` to `b`: the
+/// subinterval with the largest error estimate is halved until the total error
+/// is within `tolerance` or the subdivision limit is reached.
+pub fn integrate(f: impl Fn(f64) -> f64, a: f64, b: f64, tolerance: f64) -> f64 {
+    const MAX_INTERVALS: usize = 2000;
+    let (estimate, error) = gauss_kronrod(&f, a, b);
+    let mut intervals = vec![(a, b, estimate, error)];
+    while intervals.len() < MAX_INTERVALS {
+        let total: f64 = intervals.iter().map(|interval| interval.2).sum();
+        let total_error: f64 = intervals.iter().map(|interval| interval.3).sum();
+        if total_error <= tolerance.max(1e-14 * total.abs()) || !total.is_finite() {
+            break;
+        }
+        let worst = (0..intervals.len()).max_by(|i, j| intervals[*i].3.total_cmp(&intervals[*j].3)).unwrap();
+        let (lower, upper, _, _) = intervals.swap_remove(worst);
+        let middle = 0.5 * (lower + upper);
+        let (left, left_error) = gauss_kronrod(&f, lower, middle);
+        let (right, right_error) = gauss_kronrod(&f, middle, upper);
+        intervals.push((lower, middle, left, left_error));
+        intervals.push((middle, upper, right, right_error));
+    }
+    intervals.iter().map(|interval| interval.2).sum()
+}
+
+/// Five-point central difference approximation of `f'(at)`.
+pub fn derivative(f: impl Fn(f64) -> f64, at: f64) -> f64 {
+    let h = 1e-3 * (1.0 + at.abs());
+    (f(at - 2.0 * h) - 8.0 * f(at - h) + 8.0 * f(at + h) - f(at + 2.0 * h)) / (12.0 * h)
+}
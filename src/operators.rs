@@ -1,7 +1,13 @@
 use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use once_cell::sync::Lazy;
 // use crate::operators::Operators::{Addition, And, Assignment, BitAnd, BitOr, BitXor, Division, Eq, Exp, Ge, Gt, Keyword_DECLARE, Le, Lt, Multiplication, Neq, Or, Subtraction, TwoDots, UnaryMinus};
-use crate::tokens::{Associativity, dictionary};
-use crate::tokens::Associativity::{Left, Right};
+use crate::settings::Settings;
+use crate::solver;
+use crate::tokens::{option, Associativity, Token};
+use crate::tokens::Associativity::{Left, NoAsoc, Right};
+use crate::units;
 
 // #[derive(Copy, Clone)]
 // pub enum Operators {
@@ -39,21 +45,189 @@ use crate::tokens::Associativity::{Left, Right};
 //     return None
 // }
 
-pub(crate) fn precedence_groups<'lifetime>() -> HashMap<&'lifetime str, isize> {
-    dictionary!["=" => 9, "==" => 11, "<=" => 11, ">=" => 11, "<" => 11,
-            ">" => 11, "!=" => 11, "|" => 12, "&" => 12, "^" => 12,
-            "&&" => 13, "||" => 13, "+" => 14, "-" => 14, "/" => 15, "*" => 15, "^^" => 17, "NEGATE" => 16, ":" => 0, "declare" => 10]
+/// Name of the product inserted between adjacent operands when implicit
+/// multiplication binds tighter than `*`. It is read as `*` once the expression
+/// is ordered for evaluation.
+pub(crate) const IMPLICIT_PRODUCT: &str = "IMPLICIT";
+
+/// Where an operator is written relative to its operands.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+/// Computes an operator's result from its operands, given left to right.
+pub type Evaluate = Arc<dyn Fn(&[Token], &Settings) -> Result<Token, String> + Send + Sync>;
+
+/// Everything the lexer, the parser and the evaluator need to know about one operator.
+#[derive(Clone)]
+pub struct OperatorDefinition {
+    /// Name of the operator's tokens. It differs from `symbol` only for prefix
+    /// operators sharing a symbol with another operator, such as `NEGATE`.
+    pub name: String,
+    /// How the operator is typed. A word such as `declare` is read as a keyword.
+    pub symbol: String,
+    pub fixity: Fixity,
+    pub precedence: isize,
+    pub associativity: Associativity,
+    /// Number of operands, 1 for prefix and postfix operators.
+    pub arity: usize,
+    /// `None` for operators that only take part in parsing, like `:`.
+    pub evaluate: Option<Evaluate>,
+}
+
+impl OperatorDefinition {
+    /// A built-in operator, evaluated by `solver::apply_operator`.
+    fn builtin(name: &'static str, symbol: &str, fixity: Fixity, precedence: isize, associativity: Associativity) -> OperatorDefinition {
+        OperatorDefinition {
+            name: String::from(name),
+            symbol: String::from(symbol),
+            fixity,
+            precedence,
+            associativity,
+            arity: if fixity == Fixity::Infix { 2 } else { 1 },
+            evaluate: Some(Arc::new(move |operands, settings| solver::apply_operator(name, operands, settings))),
+        }
+    }
+}
+
+/// The operators an expression is read and evaluated with, by token name. Passed
+/// along in `Settings::operators`, which starts out as the built-in table.
+#[derive(Clone)]
+pub struct OperatorTable {
+    operators: Arc<HashMap<String, OperatorDefinition>>,
+}
+
+static BUILTIN: Lazy<OperatorTable> = Lazy::new(|| {
+    let mut table = OperatorTable::empty();
+    let infix = [
+        ("=", 9, Left), ("==", 11, NoAsoc), ("<=", 11, NoAsoc), (">=", 11, NoAsoc), ("<", 11, NoAsoc), (">", 11, NoAsoc), ("!=", 11, NoAsoc),
+        ("|", 12, Left), ("&", 12, Left), ("^", 12, Left), ("&&", 13, Left), ("||", 13, Left),
+        ("+", 14, Left), ("-", 14, Left), ("/", 15, Left), ("*", 15, Left), ("//", 15, Left), ("%", 15, Left),
+        ("^^", 17, Right), ("in", 10, Left), ("to", 10, Left),
+    ];
+    for (symbol, precedence, associativity) in infix {
+        table.register(OperatorDefinition::builtin(symbol, symbol, Fixity::Infix, precedence, associativity));
+    }
+    for (name, symbol, precedence) in [("NEGATE", "-", 16), ("NOT", "!", 16), ("COMPLEMENT", "~", 16), ("declare", "declare", 10)] {
+        table.register(OperatorDefinition::builtin(name, symbol, Fixity::Prefix, precedence, Left));
+    }
+    table.register(OperatorDefinition::builtin("!", "!", Fixity::Postfix, 18, Left));
+    table.register(OperatorDefinition {
+        evaluate: None,
+        ..OperatorDefinition::builtin(":", ":", Fixity::Infix, 0, Left)
+    });
+    table.register(OperatorDefinition {
+        name: String::from(IMPLICIT_PRODUCT),
+        ..OperatorDefinition::builtin("*", "*", Fixity::Infix, 16, Left)
+    });
+    table
+});
+
+impl OperatorTable {
+    pub fn empty() -> OperatorTable {
+        OperatorTable { operators: Arc::new(HashMap::new()) }
+    }
+
+    /// The operators every expression starts out with.
+    pub fn builtin() -> &'static OperatorTable {
+        &BUILTIN
+    }
+
+    /// Adds an operator, replacing any with the same name.
+    pub fn register(&mut self, operator: OperatorDefinition) {
+        Arc::make_mut(&mut self.operators).insert(operator.name.clone(), operator);
+    }
+
+    pub fn get(&self, name: &str) -> option!(&OperatorDefinition) {
+        self.operators.get(name)
+    }
+
+    /// Precedence of the named operator, binding tightest for unknown names. A
+    /// chain binds like its comparisons.
+    pub(crate) fn precedence(&self, name: &str) -> isize {
+        let name = chain_links(name).map_or(name, |links| links[0]);
+        self.get(name).map_or(90, |operator| operator.precedence)
+    }
+
+    pub(crate) fn associativity(&self, name: &str) -> Associativity {
+        let name = chain_links(name).map_or(name, |links| links[0]);
+        self.get(name).map_or(Left, |operator| operator.associativity)
+    }
+
+    /// Number of operands of the named operator, 2 for unknown names.
+    pub(crate) fn arity(&self, name: &str) -> usize {
+        self.get(name).map_or(2, |operator| operator.arity)
+    }
+
+    pub(crate) fn is_prefix(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|operator| operator.fixity == Fixity::Prefix)
+    }
+
+    pub(crate) fn is_postfix(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|operator| operator.fixity == Fixity::Postfix)
+    }
+
+    /// How the named operator is typed, or the name itself for unknown names.
+    pub(crate) fn spelling<'a>(&'a self, name: &'a str) -> &'a str {
+        self.get(name).map_or(name, |operator| operator.symbol.as_str())
+    }
+
+    /// Name of the prefix operator typed as `symbol`, if there is one. When several
+    /// are, the first in alphabetical order is used, so the choice does not depend
+    /// on the table's hashing.
+    pub(crate) fn prefix_named(&self, symbol: &str) -> option!(&str) {
+        self.operators.values()
+            .filter(|operator| operator.fixity == Fixity::Prefix && operator.symbol == symbol)
+            .map(|operator| operator.name.as_str())
+            .min()
+    }
+
+    /// Operator symbols that are not words, for the lexer to match.
+    pub(crate) fn symbols(&self) -> Vec<&str> {
+        let mut symbols: Vec<&str> = self.operators.values()
+            .map(|operator| operator.symbol.as_str())
+            .filter(|symbol| !symbol.chars().any(char::is_alphanumeric))
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Words the lexer reads as operators rather than as names. `in` and `to` are
+    /// left to units mode, which reads them as conversions.
+    pub(crate) fn keywords(&self) -> Vec<&str> {
+        self.operators.values()
+            .map(|operator| operator.symbol.as_str())
+            .filter(|symbol| symbol.chars().all(char::is_alphanumeric) && !units::CONVERSION_OPERATORS.contains(symbol))
+            .collect()
+    }
 }
 
-pub(crate) fn associativity<'lifetime>() -> HashMap<&'lifetime str, Associativity> {
-    dictionary!["=" => Left, "==" => Left, "<=" => Left, ">=" => Left, "<" => Left,
-            ">" => Left, "!=" => Left, "|" => Left, "&" => Left, "^" => Left,
-            "&&" => Left, "||" => Left, "+" => Left, "-" => Left, "/" => Left, "*" => Left, "^^" => Right, "NEGATE" => Left, ":" => Left]
+impl Default for OperatorTable {
+    fn default() -> OperatorTable {
+        OperatorTable::builtin().clone()
+    }
 }
 
+impl Debug for OperatorTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<&String> = self.operators.keys().collect();
+        names.sort_unstable();
+        f.debug_set().entries(names).finish()
+    }
+}
+
+/// Name of the token for a chain of comparisons such as `1 < x <= 10`: the
+/// comparisons separated by spaces, as in `< <=`, extending `chain` if it is one.
+pub(crate) fn chain_name(chain: &str, comparison: &str) -> String {
+    format!("{} {}", chain, comparison)
+}
 
-pub(crate) fn num_pars<'lifetime>() -> HashMap<&'lifetime str, usize> {
-    dictionary!["=" => 2, "==" => 2, "<=" => 2, ">=" => 2, "<" => 2,
-            ">" => 2, "!=" => 2, "|" => 2, "&" => 2, "^" => 2,
-            "&&" => 2, "||" => 2, "+" => 2, "-" => 2, "/" => 2, "*" => 2, "^^" => 2, "NEGATE" => 1, ":" => 2, "declare" => 1,"func" => 3]
+/// The comparisons of a chain, if the name is one.
+pub(crate) fn chain_links(name: &str) -> option!(Vec<&str>) {
+    let links: Vec<&str> = name.split(' ').collect();
+    (links.len() > 1).then_some(links)
 }
@@ -0,0 +1,158 @@
+use crate::solver;
+use crate::syntaxtree::reverse_polish_string_to_tree;
+use crate::tokens::{Token, TokenizedString};
+use crate::tokens::TokenType::{Function, Numeric, Operator};
+use crate::tokens::TokenUnion::FloatOf;
+
+/// Operators `solver::new_token_from_applying` always produces a value for, with
+/// their arity. Anything else is kept as it is, with its operands folded.
+const EVALUATED_OPERATORS: [(&str, usize); 10] = [
+    ("+", 2), ("-", 2), ("*", 2), ("/", 2), ("//", 2), ("%", 2), ("^^", 2), ("=", 2), ("NEGATE", 1), ("declare", 1),
+];
+
+/// Operators whose application is kept even when its value is never used.
+const STATEMENT_OPERATORS: [&str; 2] = ["=", "declare"];
+
+pub struct Folding {
+    /// The folded subexpression, in infix.
+    pub expression: String,
+    pub value: Token,
+}
+
+pub struct Optimized {
+    pub rpn: TokenizedString,
+    pub folded: Vec<Folding>,
+    /// Subexpressions whose value was never used, in infix.
+    pub eliminated: Vec<String>,
+}
+
+/// One value on the evaluation stack, as the tokens that compute it.
+struct Entry {
+    tokens: TokenizedString,
+    /// The tokens this entry replaced, when it is the result of folding.
+    source: Option<TokenizedString>,
+}
+
+impl Entry {
+    fn constant(&self) -> Option<&Token> {
+        match self.tokens.as_slice() {
+            [token] if token.token_type == Numeric => Some(token),
+            _ => None,
+        }
+    }
+
+    fn source_tokens(&self) -> &TokenizedString {
+        self.source.as_ref().unwrap_or(&self.tokens)
+    }
+}
+
+fn infix(tokens: &TokenizedString) -> String {
+    let roots = reverse_polish_string_to_tree(tokens);
+    roots.iter().map(|root| root.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+/// Folds every variable-free operator application in `rpn` by running it through
+/// `solver::new_token_from_applying` ahead of time, so `solve_reverse_polish_notation`
+/// returns the same token, bit for bit, from the optimized expression. Values
+/// left on the stack below the result are dropped unless they are statements.
+///
+/// Function calls and operators outside `EVALUATED_OPERATORS` are kept, with
+/// their operands folded. Applications that would divide by zero or otherwise
+/// give a non-finite value are kept too. Expressions that would underflow the
+/// stack are returned unchanged.
+pub fn optimize(rpn: &TokenizedString) -> Optimized {
+    let unchanged = || Optimized { rpn: rpn.clone(), folded: vec![], eliminated: vec![] };
+    let mut stack: Vec<Entry> = vec![];
+    let mut folded = vec![];
+
+    for token in rpn {
+        if token.token_type == Function {
+            if stack.len() < token.num_params {
+                return unchanged();
+            }
+            let arguments = stack.split_off(stack.len() - token.num_params);
+            stack.push(kept(arguments, token, &mut folded));
+            continue;
+        }
+        if token.token_type != Operator {
+            stack.push(Entry { tokens: vec![token.clone()], source: None });
+            continue;
+        }
+
+        let name = token.value_as_string();
+        let evaluated = EVALUATED_OPERATORS.iter().any(|(operator, arity)| *operator == name && *arity == token.num_params);
+        if stack.len() < token.num_params {
+            return unchanged();
+        }
+        let operands = stack.split_off(stack.len() - token.num_params);
+
+        if evaluated && operands.iter().all(|operand| operand.constant().is_some()) {
+            let parameters = operands.iter().rev().map(|operand| operand.constant().unwrap().clone()).collect();
+            if let Some(value) = solver::new_token_from_applying(token, parameters).filter(is_finite) {
+                let mut source: TokenizedString = operands.iter().flat_map(|operand| operand.source_tokens().clone()).collect();
+                source.push(token.clone());
+                stack.push(Entry { tokens: vec![value], source: Some(source) });
+                continue;
+            }
+        }
+
+        stack.push(kept(operands, token, &mut folded));
+    }
+
+    let mut eliminated = vec![];
+    let mut optimized = vec![];
+    let last = stack.len().saturating_sub(1);
+    for (index, entry) in stack.into_iter().enumerate() {
+        let statement = entry.source_tokens().iter().any(|token| {
+            token.token_type == Operator && STATEMENT_OPERATORS.contains(&token.value_as_string().as_str())
+        });
+        if index == last || statement {
+            record_folding(&entry, &mut folded);
+            optimized.extend(entry.tokens);
+        } else {
+            eliminated.push(infix(entry.source_tokens()));
+        }
+    }
+
+    Optimized { rpn: optimized, folded, eliminated }
+}
+
+/// The entry applying `token` to `operands` at run time, recording the operands
+/// that were folded.
+fn kept(operands: Vec<Entry>, token: &Token, folded: &mut Vec<Folding>) -> Entry {
+    let mut tokens = vec![];
+    for operand in operands {
+        record_folding(&operand, folded);
+        tokens.extend(operand.tokens);
+    }
+    tokens.push(token.clone());
+    Entry { tokens, source: None }
+}
+
+/// Whether `value` prints as a number the lexer reads back, unlike `inf` or `NaN`.
+fn is_finite(value: &Token) -> bool {
+    match value.value {
+        FloatOf(value) => value.is_finite(),
+        _ => true,
+    }
+}
+
+fn record_folding(entry: &Entry, folded: &mut Vec<Folding>) {
+    if let (Some(source), Some(value)) = (&entry.source, entry.constant()) {
+        folded.push(Folding { expression: infix(source), value: value.clone() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::string_to_rpn;
+
+    #[test]
+    fn operands_of_unevaluated_operators_are_folded() {
+        let optimized = optimize(&string_to_rpn!("x == 1 + 1").unwrap());
+        assert_eq!(Token::tokens_to_string(&optimized.rpn), "x 2 == ");
+        assert_eq!(optimized.folded.len(), 1);
+        assert_eq!(optimized.folded[0].expression, "1 + 1");
+    }
+}
@@ -0,0 +1,666 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use crate::rational;
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::TokenType::{Numeric, Operator, VariableName};
+use crate::tokens::TokenUnion::{DecimalOf, FloatOf, IntegerOf, RationalOf};
+
+/// Largest power `expand` multiplies out.
+const MAX_EXPONENT: u32 = 1000;
+
+/// Combinations of divisors tried per factor degree before factoring gives up.
+const MAX_COMBINATIONS: usize = 20_000;
+
+/// Factors with their positive powers, sorted by name.
+type Monomial = Vec<(String, u32)>;
+
+/// A sum of monomials with their exact coefficients. Subexpressions that are not
+/// polynomials, such as `sin(x)` or `x ^^ 0.5`, take part as opaque factors
+/// named after their infix form.
+#[derive(Clone, Default)]
+struct Polynomial {
+    terms: BTreeMap<Monomial, BigRational>,
+    atoms: BTreeMap<String, SyntaxTreeNode>,
+}
+
+impl Polynomial {
+    fn constant(value: BigRational) -> Polynomial {
+        let mut constant = Polynomial::default();
+        constant.add_term(vec![], value);
+        constant
+    }
+
+    fn variable(name: String) -> Polynomial {
+        let mut variable = Polynomial::default();
+        variable.add_term(vec![(name, 1)], BigRational::one());
+        variable
+    }
+
+    /// `tree` as an opaque factor, with the polynomials inside it expanded.
+    fn atom(tree: &SyntaxTreeNode) -> Result<Polynomial, String> {
+        let children = tree.children().iter().map(expand).collect::<Result<_, _>>()?;
+        let atom = SyntaxTreeNode::new(tree.value().clone(), tree.token_type(), children);
+        let name = atom.to_string();
+        let mut polynomial = Polynomial::variable(name.clone());
+        polynomial.atoms.insert(name, atom);
+        Ok(polynomial)
+    }
+
+    fn from_tree(tree: &SyntaxTreeNode) -> Result<Polynomial, String> {
+        let children = tree.children();
+        let name = tree.value_as_string();
+        let polynomial = match (tree.token_type(), name.as_str(), children.len()) {
+            (Numeric, _, 0) => match exact_value(tree) {
+                Some(number) => Polynomial::constant(number),
+                None => Polynomial::atom(tree)?,
+            },
+            (VariableName, _, 0) => Polynomial::variable(name),
+            (Operator, "NEGATE", 1) => Polynomial::from_tree(&children[0])?.negate(),
+            (Operator, "+", 2) => Polynomial::from_tree(&children[0])?.add(&Polynomial::from_tree(&children[1])?),
+            (Operator, "-", 2) => Polynomial::from_tree(&children[0])?.add(&Polynomial::from_tree(&children[1])?.negate()),
+            (Operator, "*", 2) => Polynomial::from_tree(&children[0])?.multiply(&Polynomial::from_tree(&children[1])?),
+            (Operator, "/", 2) => match Polynomial::from_tree(&children[1])?.as_constant() {
+                Some(divisor) if !divisor.is_zero() => Polynomial::from_tree(&children[0])?.scale(divisor.recip()),
+                _ => Polynomial::atom(tree)?,
+            },
+            (Operator, "^^", 2) => match Polynomial::from_tree(&children[1])?.as_constant() {
+                Some(exponent) if exponent.is_integer() && !exponent.is_negative() => {
+                    let exponent = exponent.to_integer().to_u32().filter(|exponent| *exponent <= MAX_EXPONENT)
+                        .ok_or_else(|| format!("Error. {} is too large a power to expand.", tree))?;
+                    Polynomial::from_tree(&children[0])?.power(exponent)
+                }
+                _ => Polynomial::atom(tree)?,
+            },
+            _ => Polynomial::atom(tree)?,
+        };
+        Ok(polynomial)
+    }
+
+    fn add_term(&mut self, monomial: Monomial, coefficient: BigRational) {
+        let sum = self.terms.get(&monomial).cloned().unwrap_or_else(BigRational::zero) + coefficient;
+        if sum.is_zero() {
+            self.terms.remove(&monomial);
+        } else {
+            self.terms.insert(monomial, sum);
+        }
+    }
+
+    fn add(&self, other: &Polynomial) -> Polynomial {
+        let mut sum = self.clone();
+        sum.atoms.extend(other.atoms.clone());
+        for (monomial, coefficient) in &other.terms {
+            sum.add_term(monomial.clone(), coefficient.clone());
+        }
+        sum
+    }
+
+    fn scale(&self, factor: BigRational) -> Polynomial {
+        self.multiply(&Polynomial::constant(factor))
+    }
+
+    fn negate(&self) -> Polynomial {
+        self.scale(-BigRational::one())
+    }
+
+    fn multiply(&self, other: &Polynomial) -> Polynomial {
+        let mut atoms = self.atoms.clone();
+        atoms.extend(other.atoms.clone());
+        let mut product = Polynomial { terms: BTreeMap::new(), atoms };
+        for (a, a_coefficient) in &self.terms {
+            for (b, b_coefficient) in &other.terms {
+                product.add_term(multiply_monomials(a, b), a_coefficient * b_coefficient);
+            }
+        }
+        product
+    }
+
+    fn power(&self, exponent: u32) -> Polynomial {
+        let mut result = Polynomial::constant(BigRational::one());
+        let mut base = self.clone();
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = result.multiply(&base);
+            }
+            exponent /= 2;
+            if exponent > 0 {
+                base = base.multiply(&base);
+            }
+        }
+        result
+    }
+
+    fn as_constant(&self) -> Option<BigRational> {
+        match self.terms.iter().next() {
+            None => Some(BigRational::zero()),
+            Some((monomial, coefficient)) if self.terms.len() == 1 && monomial.is_empty() => Some(coefficient.clone()),
+            _ => None,
+        }
+    }
+
+    fn monomial_tree(&self, monomial: &Monomial) -> Option<SyntaxTreeNode> {
+        monomial.iter()
+            .map(|(name, exponent)| {
+                let base = self.atoms.get(name).cloned().unwrap_or_else(|| SyntaxTreeNode::variable(name));
+                power_tree(base, *exponent)
+            })
+            .reduce(|product, factor| SyntaxTreeNode::operator("*", vec![product, factor]))
+    }
+
+    /// Terms in decreasing total degree, then alphabetically by variable.
+    fn to_tree(&self) -> SyntaxTreeNode {
+        let mut terms: Vec<(&Monomial, &BigRational)> = self.terms.iter().collect();
+        terms.sort_by(|a, b| compare_monomials(a.0, b.0));
+        sum(terms.into_iter().map(|(monomial, coefficient)| (coefficient.clone(), self.monomial_tree(monomial))).collect())
+    }
+}
+
+/// The exact value of a number literal. Floats are read as the decimal they print as.
+fn exact_value(tree: &SyntaxTreeNode) -> Option<BigRational> {
+    let from_decimal = |text: String| match text.strip_prefix('-') {
+        Some(magnitude) => rational::parse(magnitude).map(|value| -value),
+        None => rational::parse(text.as_str()),
+    };
+    match tree.value() {
+        IntegerOf(i) => Some(BigRational::from_integer(i.clone())),
+        RationalOf(r) => Some(r.clone()),
+        DecimalOf(d) => from_decimal(d.to_string()),
+        FloatOf(f) if f.is_finite() => from_decimal(f.to_string()),
+        _ => None,
+    }
+}
+
+fn number_tree(value: BigRational) -> SyntaxTreeNode {
+    SyntaxTreeNode::new(RationalOf(value), Numeric, vec![])
+}
+
+fn multiply_monomials(a: &Monomial, b: &Monomial) -> Monomial {
+    let mut product = a.clone();
+    for (name, exponent) in b {
+        match product.iter_mut().find(|factor| factor.0 == *name) {
+            Some(factor) => factor.1 += exponent,
+            None => product.push((name.clone(), *exponent)),
+        }
+    }
+    product.sort();
+    product
+}
+
+fn compare_monomials(a: &Monomial, b: &Monomial) -> Ordering {
+    let degree = |monomial: &Monomial| monomial.iter().map(|factor| factor.1).sum::<u32>();
+    degree(b).cmp(&degree(a)).then_with(|| {
+        for (x, y) in a.iter().zip(b) {
+            if x.0 != y.0 {
+                return x.0.cmp(&y.0);
+            }
+            if x.1 != y.1 {
+                return y.1.cmp(&x.1);
+            }
+        }
+        b.len().cmp(&a.len())
+    })
+}
+
+fn power_tree(base: SyntaxTreeNode, exponent: u32) -> SyntaxTreeNode {
+    if exponent == 1 {
+        return base;
+    }
+    SyntaxTreeNode::operator("^^", vec![base, SyntaxTreeNode::number(exponent as f64)])
+}
+
+fn product_tree(coefficient: BigRational, factors: Option<SyntaxTreeNode>) -> SyntaxTreeNode {
+    match factors {
+        None => number_tree(coefficient),
+        Some(factors) if coefficient.is_one() => factors,
+        Some(factors) => with_first_factor(factors, &|first| {
+            if (-&coefficient).is_one() {
+                SyntaxTreeNode::operator("NEGATE", vec![first])
+            } else {
+                SyntaxTreeNode::operator("*", vec![number_tree(coefficient.clone()), first])
+            }
+        }),
+    }
+}
+
+/// Rewrites the leftmost factor of a product, so a coefficient joins `a * b`
+/// as `2 * a * b` rather than `2 * (a * b)`.
+fn with_first_factor(product: SyntaxTreeNode, rewrite: &dyn Fn(SyntaxTreeNode) -> SyntaxTreeNode) -> SyntaxTreeNode {
+    if product.is_operator("*") {
+        let children = product.children();
+        return SyntaxTreeNode::operator("*", vec![with_first_factor(children[0].clone(), rewrite), children[1].clone()]);
+    }
+    rewrite(product)
+}
+
+/// `c1 * t1 + c2 * t2 + ...`, written with `-` for the later negative coefficients.
+fn sum(terms: Vec<(BigRational, Option<SyntaxTreeNode>)>) -> SyntaxTreeNode {
+    let mut terms = terms.into_iter();
+    let first = match terms.next() {
+        Some((coefficient, factors)) => product_tree(coefficient, factors),
+        None => number_tree(BigRational::zero()),
+    };
+    terms.fold(first, |sum, (coefficient, factors)| {
+        if coefficient.is_negative() {
+            SyntaxTreeNode::operator("-", vec![sum, product_tree(-coefficient, factors)])
+        } else {
+            SyntaxTreeNode::operator("+", vec![sum, product_tree(coefficient, factors)])
+        }
+    })
+}
+
+/// Multiplies out every product and integer power in `tree` and collects like
+/// terms, e.g. `(x + 1) ^^ 3` becomes `x ^^ 3 + 3 * x ^^ 2 + 3 * x + 1`.
+/// Coefficients are exact fractions.
+pub fn expand(tree: &SyntaxTreeNode) -> Result<SyntaxTreeNode, String> {
+    Ok(Polynomial::from_tree(tree)?.to_tree())
+}
+
+/// Expands `tree` and groups its terms by powers of `variable`, highest first,
+/// e.g. `a * x + b * x + c` becomes `(a + b) * x + c`.
+pub fn collect(tree: &SyntaxTreeNode, variable: &str) -> Result<SyntaxTreeNode, String> {
+    let polynomial = Polynomial::from_tree(tree)?;
+    let mut powers: BTreeMap<u32, Polynomial> = BTreeMap::new();
+    for (monomial, coefficient) in &polynomial.terms {
+        let power = monomial.iter().find(|factor| factor.0 == variable).map_or(0, |factor| factor.1);
+        let rest = monomial.iter().filter(|factor| factor.0 != variable).cloned().collect();
+        powers.entry(power)
+            .or_insert_with(|| Polynomial { terms: BTreeMap::new(), atoms: polynomial.atoms.clone() })
+            .add_term(rest, coefficient.clone());
+    }
+
+    let terms = powers.iter().rev().map(|(power, coefficient)| {
+        let variable = (*power > 0).then(|| power_tree(SyntaxTreeNode::variable(variable), *power));
+        let (number, factors) = match coefficient.terms.iter().next() {
+            Some((monomial, number)) if coefficient.terms.len() == 1 => (number.clone(), coefficient.monomial_tree(monomial)),
+            _ => (BigRational::one(), Some(coefficient.to_tree())),
+        };
+        let factors = match (factors, variable) {
+            (Some(factors), Some(variable)) => Some(SyntaxTreeNode::operator("*", vec![factors, variable])),
+            (factors, variable) => factors.or(variable),
+        };
+        (number, factors)
+    });
+    Ok(sum(terms.collect()))
+}
+
+/// A polynomial in at most one variable, as its coefficients from the constant term up.
+struct Univariate {
+    variable: Option<String>,
+    coefficients: Vec<BigRational>,
+}
+
+impl Univariate {
+    fn from_tree(tree: &SyntaxTreeNode) -> Result<Univariate, String> {
+        let polynomial = Polynomial::from_tree(tree)?;
+        if !polynomial.atoms.is_empty() {
+            return Err(format!("Error. {} is not a polynomial.", tree));
+        }
+        let mut variable = None;
+        let mut coefficients = vec![];
+        for (monomial, coefficient) in &polynomial.terms {
+            let power = match monomial.as_slice() {
+                [] => 0,
+                [(name, power)] if *variable.get_or_insert(name) == name => *power as usize,
+                _ => return Err(format!("Error. {} has more than one variable.", tree)),
+            };
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, BigRational::zero());
+            }
+            coefficients[power] = coefficient.clone();
+        }
+        Ok(Univariate { variable: variable.cloned(), coefficients })
+    }
+
+    fn to_tree(&self) -> SyntaxTreeNode {
+        let variable = self.variable.as_deref().unwrap_or("x");
+        let terms = self.coefficients.iter().enumerate().rev()
+            .filter(|(_, coefficient)| !coefficient.is_zero())
+            .map(|(power, coefficient)| {
+                let factors = (power > 0).then(|| power_tree(SyntaxTreeNode::variable(variable), power as u32));
+                (coefficient.clone(), factors)
+            });
+        sum(terms.collect())
+    }
+}
+
+/// The variable shared by `a` and `b`, which may each be constant.
+fn common_variable(a: &Univariate, b: &Univariate) -> Result<Option<String>, String> {
+    match (&a.variable, &b.variable) {
+        (Some(x), Some(y)) if x != y => Err(format!("Error. {} and {} are different variables.", x, y)),
+        (x, y) => Ok(x.clone().or_else(|| y.clone())),
+    }
+}
+
+/// Drops the zero coefficients at the top.
+fn trim(coefficients: &mut Vec<BigRational>) {
+    while coefficients.last().is_some_and(Zero::is_zero) {
+        coefficients.pop();
+    }
+}
+
+fn divide_coefficients(dividend: &[BigRational], divisor: &[BigRational]) -> (Vec<BigRational>, Vec<BigRational>) {
+    let degree = divisor.len() - 1;
+    let mut remainder = dividend.to_vec();
+    if remainder.len() <= degree {
+        return (vec![], remainder);
+    }
+    let mut quotient = vec![BigRational::zero(); remainder.len() - degree];
+    for index in (0..quotient.len()).rev() {
+        let factor = &remainder[index + degree] / &divisor[degree];
+        for (offset, coefficient) in divisor.iter().enumerate() {
+            remainder[index + offset] -= &factor * coefficient;
+        }
+        quotient[index] = factor;
+    }
+    remainder.truncate(degree);
+    trim(&mut remainder);
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+/// Long division of univariate polynomials, returning the quotient and remainder.
+pub fn divide(dividend: &SyntaxTreeNode, divisor: &SyntaxTreeNode) -> Result<(SyntaxTreeNode, SyntaxTreeNode), String> {
+    let (dividend, divisor) = (Univariate::from_tree(dividend)?, Univariate::from_tree(divisor)?);
+    let variable = common_variable(&dividend, &divisor)?;
+    if divisor.coefficients.is_empty() {
+        return Err(String::from("Error. Division by zero."));
+    }
+    let (quotient, remainder) = divide_coefficients(&dividend.coefficients, &divisor.coefficients);
+    let quotient = Univariate { variable: variable.clone(), coefficients: quotient };
+    let remainder = Univariate { variable, coefficients: remainder };
+    Ok((quotient.to_tree(), remainder.to_tree()))
+}
+
+/// The greatest common divisor of two univariate polynomials. It is monic,
+/// unless both have integer coefficients, in which case it is the primitive
+/// integer divisor times the gcd of their contents.
+pub fn gcd(a: &SyntaxTreeNode, b: &SyntaxTreeNode) -> Result<SyntaxTreeNode, String> {
+    let (a, b) = (Univariate::from_tree(a)?, Univariate::from_tree(b)?);
+    let variable = common_variable(&a, &b)?;
+    let (mut x, mut y) = (a.coefficients.clone(), b.coefficients.clone());
+    while !y.is_empty() {
+        let (_, remainder) = divide_coefficients(&x, &y);
+        x = y;
+        y = remainder;
+    }
+    let mut divisor: Vec<BigRational> = match x.last() {
+        Some(leading) => x.iter().map(|coefficient| coefficient / leading).collect(),
+        None => x,
+    };
+
+    if let (Some((a, 1)), Some((b, 1))) = (integer_coefficients(&a.coefficients), integer_coefficients(&b.coefficients)) {
+        if let Some((primitive, _)) = integer_coefficients(&divisor) {
+            let content = integer_gcd(content(&a), content(&b));
+            let primitive_content = content_of(&primitive);
+            divisor = primitive.iter()
+                .map(|coefficient| BigRational::from_integer(BigInt::from(coefficient / primitive_content * content)))
+                .collect();
+        }
+    }
+    Ok(Univariate { variable, coefficients: divisor }.to_tree())
+}
+
+/// Factors a univariate polynomial with rational coefficients into a constant
+/// times primitive integer polynomials that are irreducible over the rationals,
+/// e.g. `2 * x ^^ 3 - 2 * x` becomes `2 * x * (x - 1) * (x + 1)`.
+///
+/// Linear factors come from the rational root theorem and higher ones from
+/// Kronecker's method, which gives up on polynomials with too many candidate
+/// factors and leaves them whole.
+pub fn factor(tree: &SyntaxTreeNode) -> Result<SyntaxTreeNode, String> {
+    let polynomial = Univariate::from_tree(tree)?;
+    let variable = match &polynomial.variable {
+        Some(variable) => variable.clone(),
+        None => return Ok(polynomial.to_tree()),
+    };
+    let (integers, denominator) = integer_coefficients(&polynomial.coefficients)
+        .ok_or_else(|| String::from("Error. The coefficients are too large to factor."))?;
+
+    let content = content_of(&integers);
+    let mut remaining: Vec<i128> = integers.iter().map(|coefficient| coefficient / content).collect();
+    let mut factors: Vec<(Vec<i128>, u32)> = vec![];
+    let zeros = remaining.iter().take_while(|coefficient| **coefficient == 0).count();
+    if zeros > 0 {
+        factors.push((vec![0, 1], zeros as u32));
+        remaining.drain(..zeros);
+    }
+
+    let mut degree = 1;
+    while 2 * degree < remaining.len() {
+        match find_factor(&remaining, degree) {
+            Some(found) => {
+                let mut multiplicity = 0;
+                while let Some(quotient) = exact_division(&remaining, &found) {
+                    remaining = quotient;
+                    multiplicity += 1;
+                }
+                factors.push((found, multiplicity));
+            }
+            None => degree += 1,
+        }
+    }
+    if remaining.len() > 1 {
+        factors.push((remaining, 1));
+    }
+
+    let product = factors.into_iter()
+        .map(|(factor, multiplicity)| {
+            let coefficients = factor.iter().map(|coefficient| BigRational::from_integer(BigInt::from(*coefficient))).collect();
+            let factor = Univariate { variable: Some(variable.clone()), coefficients }.to_tree();
+            power_tree(factor, multiplicity)
+        })
+        .reduce(|product, factor| SyntaxTreeNode::operator("*", vec![product, factor]));
+    Ok(product_tree(BigRational::new(BigInt::from(content), BigInt::from(denominator)), product))
+}
+
+/// The coefficients as integers over their common denominator, when those fit
+/// the factor search.
+fn integer_coefficients(coefficients: &[BigRational]) -> Option<(Vec<i128>, i128)> {
+    let denominator = coefficients.iter().fold(BigInt::one(), |lcm, coefficient| lcm.lcm(coefficient.denom()));
+    let integers = coefficients.iter()
+        .map(|coefficient| (coefficient.numer() * (&denominator / coefficient.denom())).to_i128())
+        .collect::<Option<Vec<_>>>()?;
+    Some((integers, denominator.to_i128()?))
+}
+
+fn integer_gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The gcd of the coefficients, signed like the leading one.
+fn content_of(coefficients: &[i128]) -> i128 {
+    let content = coefficients.iter().fold(0, |gcd, coefficient| integer_gcd(gcd, *coefficient));
+    match coefficients.last() {
+        Some(leading) if *leading < 0 => -content,
+        _ => content.max(1),
+    }
+}
+
+fn content(coefficients: &[i128]) -> i128 {
+    content_of(coefficients).abs()
+}
+
+/// `dividend / divisor` when it leaves no remainder and has integer coefficients.
+fn exact_division(dividend: &[i128], divisor: &[i128]) -> Option<Vec<i128>> {
+    let degree = divisor.len() - 1;
+    if dividend.len() <= degree {
+        return None;
+    }
+    let mut remainder = dividend.to_vec();
+    let mut quotient = vec![0; dividend.len() - degree];
+    for index in (0..quotient.len()).rev() {
+        if remainder[index + degree] % divisor[degree] != 0 {
+            return None;
+        }
+        let factor = remainder[index + degree] / divisor[degree];
+        quotient[index] = factor;
+        for (offset, coefficient) in divisor.iter().enumerate() {
+            remainder[index + offset] = remainder[index + offset].checked_sub(factor.checked_mul(*coefficient)?)?;
+        }
+    }
+    remainder.iter().all(|coefficient| *coefficient == 0).then_some(quotient)
+}
+
+fn evaluate(coefficients: &[i128], x: i128) -> Option<i128> {
+    coefficients.iter().rev().try_fold(0i128, |value, coefficient| value.checked_mul(x)?.checked_add(*coefficient))
+}
+
+/// The positive divisors of `n`, or nothing when `n` is zero or too large to search.
+fn divisors(n: i128) -> Option<Vec<i128>> {
+    let n = n.abs();
+    if n == 0 || n > 1_000_000_000_000 {
+        return None;
+    }
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut divisor = 1;
+    while divisor * divisor <= n {
+        if n % divisor == 0 {
+            small.push(divisor);
+            if divisor * divisor != n {
+                large.push(n / divisor);
+            }
+        }
+        divisor += 1;
+    }
+    small.extend(large.into_iter().rev());
+    Some(small)
+}
+
+/// A primitive factor of `polynomial` of exactly `degree`, with a positive
+/// leading coefficient.
+fn find_factor(polynomial: &[i128], degree: usize) -> Option<Vec<i128>> {
+    if degree == 1 {
+        return find_linear_factor(polynomial);
+    }
+
+    // A factor's values at degree + 1 points divide the polynomial's values
+    // there, and those values pin the factor down.
+    let points: Vec<i128> = (0..=degree as i128).map(|index| index - degree as i128 / 2).collect();
+    let mut choices = vec![];
+    for (index, point) in points.iter().enumerate() {
+        let positive = divisors(evaluate(polynomial, *point)?)?;
+        let mut values = positive.clone();
+        if index > 0 {
+            values.extend(positive.iter().map(|divisor| -divisor));
+        }
+        choices.push(values);
+    }
+    let combinations = choices.iter().try_fold(1usize, |count, values| count.checked_mul(values.len()))?;
+    if combinations > MAX_COMBINATIONS {
+        return None;
+    }
+
+    let mut selection = vec![0; points.len()];
+    loop {
+        let values: Vec<i128> = selection.iter().zip(&choices).map(|(index, values)| values[*index]).collect();
+        if let Some(candidate) = interpolate(&points, &values) {
+            if candidate.len() == degree + 1 && exact_division(polynomial, &candidate).is_some() {
+                let content = content_of(&candidate);
+                return Some(candidate.iter().map(|coefficient| coefficient / content).collect());
+            }
+        }
+
+        let mut position = 0;
+        loop {
+            if position == selection.len() {
+                return None;
+            }
+            selection[position] += 1;
+            if selection[position] < choices[position].len() {
+                break;
+            }
+            selection[position] = 0;
+            position += 1;
+        }
+    }
+}
+
+/// `q * x - p` for a rational root `p / q`, by the rational root theorem.
+fn find_linear_factor(polynomial: &[i128]) -> Option<Vec<i128>> {
+    let constants = divisors(polynomial[0])?;
+    let leading = divisors(*polynomial.last()?)?;
+    for q in &leading {
+        for p in &constants {
+            if integer_gcd(*p, *q) != 1 {
+                continue;
+            }
+            for candidate in [vec![-p, *q], vec![*p, *q]] {
+                if exact_division(polynomial, &candidate).is_some() {
+                    return Some(candidate);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The polynomial with integer coefficients through `(points[i], values[i])`,
+/// if there is one, from the Lagrange form scaled by a common denominator.
+fn interpolate(points: &[i128], values: &[i128]) -> Option<Vec<i128>> {
+    let mut scaled = vec![0i128; points.len()];
+    let denominators: Vec<i128> = points.iter().enumerate()
+        .map(|(i, x)| points.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, y)| x - y).product())
+        .collect();
+    let common = denominators.iter().try_fold(1, |lcm: i128, denominator| {
+        (lcm / integer_gcd(lcm, *denominator)).checked_mul(*denominator)
+    })?;
+
+    for (i, value) in values.iter().enumerate() {
+        let mut basis = vec![1i128];
+        for (j, point) in points.iter().enumerate() {
+            if j != i {
+                let mut next = vec![0; basis.len() + 1];
+                for (power, coefficient) in basis.iter().enumerate() {
+                    next[power + 1] += coefficient;
+                    next[power] -= coefficient * point;
+                }
+                basis = next;
+            }
+        }
+        let weight = value.checked_mul(common / denominators[i])?;
+        for (power, coefficient) in basis.iter().enumerate() {
+            scaled[power] = scaled[power].checked_add(coefficient.checked_mul(weight)?)?;
+        }
+    }
+
+    if scaled.iter().any(|coefficient| coefficient % common != 0) {
+        return None;
+    }
+    let mut coefficients: Vec<i128> = scaled.iter().map(|coefficient| coefficient / common).collect();
+    while coefficients.last() == Some(&0) {
+        coefficients.pop();
+    }
+    Some(coefficients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntaxtree::string_to_tree;
+
+    fn expanded(source: &str) -> String {
+        expand(&string_to_tree(source).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn expansion_is_exact() {
+        assert!(expanded("(x + 1) ^^ 60").contains(" + 118264581564861424 * x ^^ 30 + "));
+        assert_eq!(expanded("(x / 3 + 1 / 3) ^^ 2"), "1/9 * x ^^ 2 + 2/9 * x + 1/9");
+        assert!(expanded("(x + 1) ^^ 100").starts_with("x ^^ 100 + 100 * x ^^ 99 + 4950 * x ^^ 98"));
+    }
+
+    #[test]
+    fn factoring_keeps_fractions_exact() {
+        let factored = factor(&string_to_tree("x ^^ 2 / 4 - 1 / 9").unwrap()).unwrap();
+        assert_eq!(factored.to_string(), "1/36 * (3 * x - 2) * (3 * x + 2)");
+    }
+}
@@ -0,0 +1,210 @@
+use crate::operators::OperatorTable;
+use crate::symbols::{greek_letter, unicode_symbol, MINUS};
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::Associativity::{Left, Right};
+use crate::tokens::TokenType::{Function, Numeric, Operator, VariableName};
+
+const SUPERSCRIPTS: [(char, char); 39] = [
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'),
+    ('8', '⁸'), ('9', '⁹'), ('+', '⁺'), ('−', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'),
+    ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'), ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'),
+    ('i', 'ⁱ'), ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'),
+    ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'),
+];
+
+fn is_operator(tree: &SyntaxTreeNode, operator: &str) -> bool {
+    tree.token_type() == Operator && tree.value_as_string() == operator
+}
+
+fn is_negative_number(tree: &SyntaxTreeNode) -> bool {
+    tree.as_number().is_some_and(|n| n.is_sign_negative())
+}
+
+fn needs_parentheses(child_precedence: isize, parent_precedence: isize, tie_needs_parentheses: bool) -> bool {
+    child_precedence < parent_precedence || (child_precedence == parent_precedence && tie_needs_parentheses)
+}
+
+/// Renders the tree on one line for terminals, e.g. `x² + √y` or `a·b ≤ 2π`.
+pub fn to_unicode(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let name = tree.value_as_string();
+    let children = tree.children();
+    match tree.token_type() {
+        Operator if children.len() == 2 => {
+            let (left, right) = (&children[0], &children[1]);
+            let precedence = tree.precedence(operators);
+            if name == "^^" {
+                let base = unicode_operand(left, operators, precedence, true);
+                let exponent = to_unicode(right, operators);
+                // An exponent only partly raised, as in `a^bᶜ`, would read as a different power.
+                return match superscript(exponent.as_str()) {
+                    Some(raised) => format!("{}{}", base, raised),
+                    None => format!("{}^({})", base, exponent),
+                };
+            }
+            let associativity = operators.associativity(name.as_str());
+            let symbol = unicode_symbol(name.as_str());
+            let separator = if symbol == "·" { "" } else { " " };
+            format!("{}{}{}{}{}",
+                    unicode_operand(left, operators, precedence, associativity != Left),
+                    separator, symbol, separator,
+                    unicode_operand(right, operators, precedence, associativity != Right))
+        }
+
+        Operator if children.len() == 1 => {
+            let operand = unicode_operand(&children[0], operators, tree.precedence(operators), true);
+            match name.as_str() {
+                "NEGATE" => format!("{}{}", MINUS, operand),
+                "NOT" => format!("¬{}", operand),
+                "COMPLEMENT" => format!("~{}", operand),
+                "!" => format!("{}!", operand),
+                _ => format!("{} {}", name, operand),
+            }
+        }
+
+        Function => {
+            let arguments: Vec<String> = children.iter().map(|child| to_unicode(child, operators)).collect();
+            match name.as_str() {
+                "sqrt" if children.len() == 1 => {
+                    if children[0].token_type() == Operator || is_negative_number(&children[0]) {
+                        format!("√({})", arguments[0])
+                    } else {
+                        format!("√{}", arguments[0])
+                    }
+                }
+                "abs" if children.len() == 1 => format!("|{}|", arguments[0]),
+                "vector" => format!("[{}]", arguments.join(", ")),
+                _ => format!("{}({})", name, arguments.join(", ")),
+            }
+        }
+
+        VariableName => greek_letter(name.as_str()).map(String::from).unwrap_or(name),
+
+        Numeric if is_negative_number(tree) => name.replacen('-', MINUS, 1),
+
+        _ => name,
+    }
+}
+
+fn unicode_operand(child: &SyntaxTreeNode, operators: &OperatorTable, parent_precedence: isize, tie_needs_parentheses: bool) -> String {
+    if needs_parentheses(child.precedence(operators), parent_precedence, tie_needs_parentheses) {
+        format!("({})", to_unicode(child, operators))
+    } else {
+        to_unicode(child, operators)
+    }
+}
+
+/// Raises already rendered text, or `None` when some character has no superscript form.
+fn superscript(text: &str) -> Option<String> {
+    text.chars()
+        .filter(|c| *c != ' ')
+        .map(|c| SUPERSCRIPTS.iter().find(|(plain, _)| *plain == c).map(|(_, raised)| *raised))
+        .collect()
+}
+
+/// Renders the tree as a Presentation MathML `<math>` element.
+pub fn to_mathml(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    format!("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>", mathml_element(tree, operators))
+}
+
+/// Every node renders to exactly one element so it can be used as an argument of
+/// `<mfrac>` and `<msup>`.
+fn mathml_element(tree: &SyntaxTreeNode, operators: &OperatorTable) -> String {
+    let name = tree.value_as_string();
+    let children = tree.children();
+    match tree.token_type() {
+        Operator if children.len() == 2 => {
+            let (left, right) = (&children[0], &children[1]);
+            let precedence = mathml_precedence(tree, operators);
+            match name.as_str() {
+                "/" => format!("<mfrac>{}{}</mfrac>", mathml_element(left, operators), mathml_element(right, operators)),
+                "^^" => {
+                    let base = if left.token_type() == Operator || is_negative_number(left) {
+                        parenthesized(mathml_element(left, operators))
+                    } else {
+                        mathml_element(left, operators)
+                    };
+                    format!("<msup>{}{}</msup>", base, mathml_element(right, operators))
+                }
+                _ => {
+                    let associativity = operators.associativity(name.as_str());
+                    format!("<mrow>{}<mo>{}</mo>{}</mrow>",
+                            mathml_operand(left, operators, precedence, associativity != Left),
+                            escape(unicode_symbol(name.as_str())),
+                            mathml_operand(right, operators, precedence, associativity != Right))
+                }
+            }
+        }
+
+        Operator if children.len() == 1 => {
+            let operand = mathml_operand(&children[0], operators, mathml_precedence(tree, operators), true);
+            match name.as_str() {
+                "NEGATE" => format!("<mrow><mo>{}</mo>{}</mrow>", MINUS, operand),
+                "NOT" => format!("<mrow><mo>&#xAC;</mo>{}</mrow>", operand),
+                "COMPLEMENT" => format!("<mrow><mo>~</mo>{}</mrow>", operand),
+                "!" => format!("<mrow>{}<mo>!</mo></mrow>", operand),
+                _ => format!("<mrow><mi>{}</mi>{}</mrow>", name, operand),
+            }
+        }
+
+        Function => {
+            let arguments: Vec<String> = children.iter().map(|child| mathml_element(child, operators)).collect();
+            match name.as_str() {
+                "sqrt" if children.len() == 1 => format!("<msqrt>{}</msqrt>", arguments[0]),
+                "abs" if children.len() == 1 => format!("<mrow><mo>|</mo>{}<mo>|</mo></mrow>", arguments[0]),
+                "vector" => format!("<mrow><mo>[</mo>{}<mo>]</mo></mrow>", arguments.join("<mo>,</mo>")),
+                _ => format!("<mrow><mi>{}</mi><mo>&#x2061;</mo>{}</mrow>",
+                             name, parenthesized(arguments.join("<mo>,</mo>"))),
+            }
+        }
+
+        VariableName => format!("<mi>{}</mi>", greek_letter(name.as_str()).map(String::from).unwrap_or(name)),
+
+        Numeric if is_negative_number(tree) => format!("<mrow><mo>{}</mo><mn>{}</mn></mrow>", MINUS, &name[1..]),
+
+        Numeric => format!("<mn>{}</mn>", name),
+
+        _ => format!("<mi>{}</mi>", escape(name.as_str())),
+    }
+}
+
+/// Fractions are laid out vertically and never need parentheses around them.
+fn mathml_precedence(tree: &SyntaxTreeNode, operators: &OperatorTable) -> isize {
+    if is_operator(tree, "/") {
+        return isize::MAX;
+    }
+    tree.precedence(operators)
+}
+
+fn mathml_operand(child: &SyntaxTreeNode, operators: &OperatorTable, parent_precedence: isize, tie_needs_parentheses: bool) -> String {
+    if needs_parentheses(mathml_precedence(child, operators), parent_precedence, tie_needs_parentheses) {
+        parenthesized(mathml_element(child, operators))
+    } else {
+        mathml_element(child, operators)
+    }
+}
+
+fn parenthesized(content: String) -> String {
+    format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", content)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntaxtree::string_to_tree;
+
+    fn unicode(source: &str) -> String {
+        to_unicode(&string_to_tree(source).unwrap(), OperatorTable::builtin())
+    }
+
+    #[test]
+    fn exponents_are_raised_whole_or_parenthesized() {
+        assert_eq!(unicode("x ^^ (n + 1)"), "xⁿ⁺¹");
+        assert_eq!(unicode("a ^^ (b ^^ c)"), "a^(bᶜ)");
+        assert_eq!(unicode("x ^^ 2 ^^ 3"), "x^(2³)");
+        assert_eq!(unicode("(a ^^ b) ^^ c"), "(aᵇ)ᶜ");
+    }
+}
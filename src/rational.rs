@@ -0,0 +1,92 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+
+/// Largest power `^^` raises a fraction to exactly.
+const MAX_EXPONENT: i32 = 100_000;
+
+/// Reads a decimal literal such as `0.1` as the exact fraction `1/10`.
+pub fn parse(literal: &str) -> Option<BigRational> {
+    let (literal, exponent) = match literal.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok().filter(|exponent| exponent.abs() <= MAX_EXPONENT)?),
+        None => (literal, 0),
+    };
+    let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let digits = format!("{}{}", whole, fraction);
+    if !digits.chars().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    let numerator: BigInt = digits.parse().ok()?;
+    let denominator = BigInt::from(10).pow(fraction.len());
+    let scale = BigRational::from_integer(BigInt::from(10).pow(exponent.unsigned_abs()));
+    let value = BigRational::new(numerator, denominator);
+    Some(if exponent < 0 { value / scale } else { value * scale })
+}
+
+/// Applies `+ - * / // %` or `^^` to `a` and `b` exactly. Gives `None` when the
+/// result is not a fraction, as for division by zero or non-integer powers.
+pub fn apply(operator: &str, a: &BigRational, b: &BigRational) -> Option<BigRational> {
+    match operator {
+        "+" => Some(a + b),
+        "-" => Some(a - b),
+        "*" => Some(a * b),
+        "/" if !b.is_zero() => Some(a / b),
+        "//" if !b.is_zero() => Some((a / b).floor()),
+        "%" if !b.is_zero() => Some(a - b * (a / b).floor()),
+        "^^" if b.is_integer() => {
+            let exponent = b.to_integer().to_i32().filter(|exponent| exponent.abs() <= MAX_EXPONENT)?;
+            if a.is_zero() && exponent < 0 {
+                return None;
+            }
+            Some(Pow::pow(a, exponent))
+        }
+        _ => None,
+    }
+}
+
+pub fn to_f64(value: &BigRational) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// `value` as a decimal rounded to at most `digits` places, half away from
+/// zero, without trailing zeros.
+pub fn to_decimal(value: &BigRational, digits: usize) -> String {
+    let scale = BigInt::from(10).pow(digits);
+    let scaled = (value.abs() * BigRational::from_integer(scale)).round().to_integer().to_string();
+    let padded = format!("{:0>width$}", scaled, width = digits + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - digits);
+    let fraction = fraction.trim_end_matches('0');
+    let sign = if value.is_negative() && (whole != "0" || !fraction.is_empty()) { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction(literal: &str) -> BigRational {
+        parse(literal).unwrap()
+    }
+
+    #[test]
+    fn decimal_literals_add_exactly() {
+        assert_eq!(apply("+", &fraction("0.1"), &fraction("0.2")), Some(fraction("0.3")));
+        let third = apply("/", &fraction("1"), &fraction("3")).unwrap();
+        assert_eq!(apply("*", &third, &fraction("3")), Some(fraction("1")));
+        assert_eq!(to_decimal(&third, 5), "0.33333");
+    }
+
+    #[test]
+    fn inexact_results_are_refused() {
+        assert_eq!(apply("/", &fraction("1"), &fraction("0")), None);
+        assert_eq!(apply("^^", &fraction("2"), &fraction("0.5")), None);
+        assert_eq!(apply("^^", &fraction("0.5"), &-fraction("2")), Some(fraction("4")));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::decimal::Context;
+use crate::operators::OperatorTable;
+
+/// How numeric literals are read and arithmetic on them is carried out.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum NumberMode {
+    #[default]
+    Float,
+    /// Literals are exact fractions of big integers, and `+ - * /` and integer
+    /// powers of them stay exact. Anything else falls back to `f64`.
+    Rational,
+    /// Literals are read digit for digit as decimals, and every arithmetic result
+    /// is rounded to the decimal context's precision.
+    Decimal,
+}
+
+/// Whether operands written next to each other, as in `2x` or `(a + 1)(a - 1)`,
+/// are multiplied, and how tightly that product binds.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ImplicitMultiplication {
+    /// Adjacent operands are an error.
+    #[default]
+    Off,
+    /// Binds like `*`, so `1/2x` is `(1/2) * x`.
+    SameAsProduct,
+    /// Binds tighter than `*` and `/` but looser than `^^`, so `1/2x` is
+    /// `1/(2 * x)` and `2x^^2` is `2 * x^^2`.
+    Tighter,
+}
+
+/// Options for tokenizing and evaluating an expression.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub number_mode: NumberMode,
+    pub decimal: Context,
+    /// Reads `i` and `j` as the imaginary unit and lets operators and built-ins
+    /// return complex results, e.g. `sqrt(-4)` is `2i` rather than NaN.
+    pub complex: bool,
+    /// Reads unit symbols such as `km` or `s` as quantities and checks that sums
+    /// and conversions only combine quantities of the same dimension.
+    pub units: bool,
+    /// Reads `1 < x <= 10` as `1 < x && x <= 10`, with `x` evaluated once, rather
+    /// than rejecting comparisons chained without parentheses.
+    pub chained_comparisons: bool,
+    pub implicit_multiplication: ImplicitMultiplication,
+    /// Operators the expression is lexed, parsed and evaluated with.
+    pub operators: OperatorTable,
+}
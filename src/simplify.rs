@@ -0,0 +1,372 @@
+use crate::solver;
+use crate::syntaxtree::SyntaxTreeNode;
+use crate::tokens::Token;
+use crate::tokens::TokenType::{Numeric, Operator};
+use crate::tokens::TokenUnion::{FloatOf, StrOf};
+
+/// Upper bound on whole-tree rewrite passes, in case a custom rule set never settles.
+const MAX_PASSES: usize = 100;
+
+/// A named rewrite. `rewrite` looks only at the node it is given (whose children
+/// are already simplified) and returns the replacement when the rule applies.
+#[derive(Copy, Clone)]
+pub struct Rule {
+    pub name: &'static str,
+    pub rewrite: fn(&SyntaxTreeNode) -> Option<SyntaxTreeNode>,
+}
+
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule { name: "fold-constants", rewrite: fold_constants },
+        Rule { name: "add-zero", rewrite: add_zero },
+        Rule { name: "multiply-one", rewrite: multiply_one },
+        Rule { name: "multiply-zero", rewrite: multiply_zero },
+        Rule { name: "power-identities", rewrite: power_identities },
+        Rule { name: "double-negation", rewrite: double_negation },
+        Rule { name: "subtract-self", rewrite: subtract_self },
+        Rule { name: "divide-self", rewrite: divide_self },
+        Rule { name: "collect-like-terms", rewrite: collect_like_terms },
+        Rule { name: "collect-like-factors", rewrite: collect_like_factors },
+        Rule { name: "add-negation", rewrite: add_negation },
+    ]
+}
+
+/// The default rules with the given names, in default order. Unknown names are reported.
+pub fn rules_named(names: &[&str]) -> Result<Vec<Rule>, String> {
+    let rules = default_rules();
+    if let Some(unknown) = names.iter().find(|name| !rules.iter().any(|rule| rule.name == **name)) {
+        return Err(format!("Error. Unknown simplification rule {}.", unknown));
+    }
+    Ok(rules.into_iter().filter(|rule| names.contains(&rule.name)).collect())
+}
+
+pub fn simplify(tree: &SyntaxTreeNode) -> SyntaxTreeNode {
+    simplify_with(tree, &default_rules())
+}
+
+/// Rewrites bottom-up with `rules`, in order, until no rule applies anywhere.
+pub fn simplify_with(tree: &SyntaxTreeNode, rules: &[Rule]) -> SyntaxTreeNode {
+    let mut current = tree.clone();
+    for _ in 0..MAX_PASSES {
+        let next = rewrite_once(&current, rules);
+        if next == current {
+            break;
+        }
+        current = next;
+    }
+    current
+}
+
+fn rewrite_once(tree: &SyntaxTreeNode, rules: &[Rule]) -> SyntaxTreeNode {
+    let children = tree.children().iter().map(|child| rewrite_once(child, rules)).collect();
+    let node = SyntaxTreeNode::new(tree.value().clone(), tree.token_type(), children);
+    rules.iter()
+        .find_map(|rule| (rule.rewrite)(&node))
+        .unwrap_or(node)
+}
+
+fn number(value: f64) -> SyntaxTreeNode {
+    SyntaxTreeNode::number(value)
+}
+
+fn operands<'a>(tree: &'a SyntaxTreeNode, operator: &str) -> Option<(&'a SyntaxTreeNode, &'a SyntaxTreeNode)> {
+    if tree.is_operator(operator) && tree.children().len() == 2 {
+        return Some((&tree.children()[0], &tree.children()[1]));
+    }
+    None
+}
+
+/// Evaluates operators whose operands are all literals, with the same semantics as
+/// `solver::new_token_from_applying`. Results that would not print as a literal
+/// (infinities, NaN) are left unfolded.
+fn fold_constants(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if tree.token_type() != Operator || tree.children().is_empty() {
+        return None;
+    }
+    let mut parameters = vec![];
+    for child in tree.children().iter().rev() {
+        parameters.push(Token { value: FloatOf(child.as_number()?), token_type: Numeric, num_params: 0 });
+    }
+    let operator = Token {
+        value: StrOf(tree.value_as_string()),
+        token_type: Operator,
+        num_params: tree.children().len(),
+    };
+    match solver::new_token_from_applying(&operator, parameters)?.value {
+        FloatOf(result) if result.is_finite() => Some(number(result)),
+        _ => None,
+    }
+}
+
+fn add_zero(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if let Some((a, b)) = operands(tree, "+") {
+        if a.as_number() == Some(0.0) {
+            return Some(b.clone());
+        }
+        if b.as_number() == Some(0.0) {
+            return Some(a.clone());
+        }
+    }
+    if let Some((a, b)) = operands(tree, "-") {
+        if b.as_number() == Some(0.0) {
+            return Some(a.clone());
+        }
+        if a.as_number() == Some(0.0) {
+            return Some(SyntaxTreeNode::operator("NEGATE", vec![b.clone()]));
+        }
+    }
+    None
+}
+
+fn multiply_one(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if let Some((a, b)) = operands(tree, "*") {
+        if a.as_number() == Some(1.0) {
+            return Some(b.clone());
+        }
+        if b.as_number() == Some(1.0) {
+            return Some(a.clone());
+        }
+    }
+    if let Some((a, b)) = operands(tree, "/") {
+        if b.as_number() == Some(1.0) {
+            return Some(a.clone());
+        }
+        if b.as_number() == Some(-1.0) {
+            return Some(SyntaxTreeNode::operator("NEGATE", vec![a.clone()]));
+        }
+    }
+    None
+}
+
+fn multiply_zero(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if let Some((a, b)) = operands(tree, "*") {
+        if a.as_number() == Some(0.0) || b.as_number() == Some(0.0) {
+            return Some(number(0.0));
+        }
+    }
+    if let Some((a, b)) = operands(tree, "/") {
+        if a.as_number() == Some(0.0) && b.as_number().is_some_and(|b| b != 0.0) {
+            return Some(number(0.0));
+        }
+    }
+    None
+}
+
+fn power_identities(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    let (base, exponent) = operands(tree, "^^")?;
+    match (base.as_number(), exponent.as_number()) {
+        (_, Some(1.0)) => Some(base.clone()),
+        (_, Some(0.0)) | (Some(1.0), _) => Some(number(1.0)),
+        _ => None,
+    }
+}
+
+fn double_negation(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if tree.is_operator("NEGATE") && tree.children()[0].is_operator("NEGATE") {
+        return Some(tree.children()[0].children()[0].clone());
+    }
+    None
+}
+
+fn subtract_self(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    let (a, b) = operands(tree, "-")?;
+    if a == b {
+        return Some(number(0.0));
+    }
+    None
+}
+
+fn divide_self(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    let (a, b) = operands(tree, "/")?;
+    if a == b && a.as_number() != Some(0.0) {
+        return Some(number(1.0));
+    }
+    None
+}
+
+/// Splits a product into its numeric coefficient and the remaining factors,
+/// e.g. `3 * x * 2` into `6` and `x`, and `-x / 4` into `-0.25` and `x`.
+fn coefficient_and_core(term: &SyntaxTreeNode) -> (f64, Option<SyntaxTreeNode>) {
+    if let Some(value) = term.as_number() {
+        return (value, None);
+    }
+    if term.is_operator("NEGATE") {
+        let (coefficient, core) = coefficient_and_core(&term.children()[0]);
+        return (-coefficient, core);
+    }
+    if let Some((a, b)) = operands(term, "/") {
+        if let Some(divisor) = b.as_number().filter(|divisor| *divisor != 0.0) {
+            let (coefficient, core) = coefficient_and_core(a);
+            return (coefficient / divisor, core);
+        }
+    }
+    if let Some((a, b)) = operands(term, "*") {
+        let (left_coefficient, left_core) = coefficient_and_core(a);
+        let (right_coefficient, right_core) = coefficient_and_core(b);
+        let core = match (left_core, right_core) {
+            (Some(left), Some(right)) => Some(SyntaxTreeNode::operator("*", vec![left, right])),
+            (left, right) => left.or(right),
+        };
+        return (left_coefficient * right_coefficient, core);
+    }
+    (1.0, Some(term.clone()))
+}
+
+fn sum_terms(tree: &SyntaxTreeNode, sign: f64, terms: &mut Vec<(f64, SyntaxTreeNode)>) {
+    if let Some((a, b)) = operands(tree, "+") {
+        sum_terms(a, sign, terms);
+        sum_terms(b, sign, terms);
+    } else if let Some((a, b)) = operands(tree, "-") {
+        sum_terms(a, sign, terms);
+        sum_terms(b, -sign, terms);
+    } else {
+        terms.push((sign, tree.clone()));
+    }
+}
+
+/// `2 * x + y + 3 * x` to `5 * x + y`, and `x + 1 + 2` to `x + 3`.
+fn collect_like_terms(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if !(tree.is_operator("+") || tree.is_operator("-")) {
+        return None;
+    }
+    let mut terms = vec![];
+    sum_terms(tree, 1.0, &mut terms);
+
+    let mut groups: Vec<(f64, Option<SyntaxTreeNode>)> = vec![];
+    for (sign, term) in &terms {
+        let (coefficient, core) = coefficient_and_core(term);
+        match groups.iter_mut().find(|(_, existing)| *existing == core) {
+            Some(group) => group.0 += sign * coefficient,
+            None => groups.push((sign * coefficient, core)),
+        }
+    }
+    if groups.len() == terms.len() {
+        return None;
+    }
+
+    let mut result: Option<SyntaxTreeNode> = None;
+    for (coefficient, core) in groups.into_iter().filter(|(coefficient, _)| *coefficient != 0.0) {
+        result = Some(match result {
+            None => scaled(coefficient, core),
+            Some(sum) if coefficient < 0.0 => SyntaxTreeNode::operator("-", vec![sum, scaled(-coefficient, core)]),
+            Some(sum) => SyntaxTreeNode::operator("+", vec![sum, scaled(coefficient, core)]),
+        });
+    }
+    Some(result.unwrap_or_else(|| number(0.0)))
+}
+
+fn scaled(coefficient: f64, core: Option<SyntaxTreeNode>) -> SyntaxTreeNode {
+    match core {
+        None => number(coefficient),
+        Some(core) if coefficient == 1.0 => core,
+        Some(core) if coefficient == -1.0 => SyntaxTreeNode::operator("NEGATE", vec![core]),
+        Some(core) => SyntaxTreeNode::operator("*", vec![number(coefficient), core]),
+    }
+}
+
+fn product_factors(tree: &SyntaxTreeNode, factors: &mut Vec<SyntaxTreeNode>) {
+    if let Some((a, b)) = operands(tree, "*") {
+        product_factors(a, factors);
+        product_factors(b, factors);
+    } else if tree.is_operator("NEGATE") {
+        factors.push(number(-1.0));
+        product_factors(&tree.children()[0], factors);
+    } else {
+        factors.push(tree.clone());
+    }
+}
+
+/// Multiplies the numeric factors of a product into one leading coefficient and
+/// merges repeated bases: `x * 2 * y * x * 3` to `6 * x ^^ 2 * y`. Products where
+/// nothing combines are left in their order.
+fn collect_like_factors(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    if !tree.is_operator("*") {
+        return None;
+    }
+    let mut factors = vec![];
+    product_factors(tree, &mut factors);
+
+    let mut coefficient = 1.0;
+    let mut numbers = 0;
+    let mut merged = false;
+    let mut groups: Vec<(SyntaxTreeNode, f64)> = vec![];
+    for factor in &factors {
+        if let Some(value) = factor.as_number() {
+            coefficient *= value;
+            numbers += 1;
+            continue;
+        }
+        let (base, exponent) = match operands(factor, "^^").and_then(|(base, exponent)| Some((base, exponent.as_number()?))) {
+            Some((base, exponent)) => (base.clone(), exponent),
+            None => (factor.clone(), 1.0),
+        };
+        match groups.iter_mut().find(|(existing, _)| *existing == base) {
+            Some(group) => {
+                group.1 += exponent;
+                merged = true;
+            }
+            None => groups.push((base, exponent)),
+        }
+    }
+    if !merged && numbers <= 1 {
+        return None;
+    }
+    let mut product: Vec<SyntaxTreeNode> = groups.into_iter()
+        .map(|(base, exponent)| match exponent {
+            1.0 => base,
+            exponent => SyntaxTreeNode::operator("^^", vec![base, number(exponent)]),
+        })
+        .collect();
+    if product.is_empty() || (coefficient != 1.0 && coefficient != -1.0) {
+        product.insert(0, number(coefficient));
+    } else if coefficient == -1.0 {
+        product[0] = SyntaxTreeNode::operator("NEGATE", vec![product[0].clone()]);
+    }
+    let product = product.into_iter()
+        .reduce(|product, factor| SyntaxTreeNode::operator("*", vec![product, factor]))
+        .unwrap();
+    if product == *tree {
+        return None;
+    }
+    Some(product)
+}
+
+/// `a + -b` to `a - b`, and `a - -b` to `a + b`.
+fn add_negation(tree: &SyntaxTreeNode) -> Option<SyntaxTreeNode> {
+    let (a, b, negated) = match (operands(tree, "+"), operands(tree, "-")) {
+        (Some((a, b)), _) => (a, b, "-"),
+        (_, Some((a, b))) => (a, b, "+"),
+        _ => return None,
+    };
+    if b.is_operator("NEGATE") {
+        return Some(SyntaxTreeNode::operator(negated, vec![a.clone(), b.children()[0].clone()]));
+    }
+    if let Some(value) = b.as_number().filter(|value| *value < 0.0) {
+        return Some(SyntaxTreeNode::operator(negated, vec![a.clone(), number(-value)]));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntaxtree::string_to_tree;
+
+    fn simplified(source: &str) -> String {
+        simplify(&string_to_tree(source).unwrap()).to_string()
+    }
+
+    #[test]
+    fn zero_is_only_divided_by_nonzero_numbers() {
+        assert_eq!(simplified("0 / 2"), "0");
+        assert_eq!(simplified("0 / 0"), "0 / 0");
+        assert_eq!(simplified("0 / x"), "0 / x");
+    }
+
+    #[test]
+    fn factors_are_reordered_only_when_they_combine() {
+        assert_eq!(simplified("x * 2"), "x * 2");
+        assert_eq!(simplified("x * 2 * y * x * 3"), "6 * x ^^ 2 * y");
+        assert_eq!(simplified("x * -y + x * y"), "0");
+    }
+}
@@ -1,16 +1,140 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::Range;
-use once_cell::unsync::Lazy;
-use crate::tokens::{dictionary, option, TokenizedString};
+use std::f64::consts;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::FromPrimitive;
+use crate::tokens::{option, TokenizedString};
 use crate::tokens::Token;
+use crate::complex;
+use crate::decimal;
+use crate::decimal::Decimal;
+use crate::integer;
+use crate::matrix;
+use crate::numeric;
+use crate::operators;
+use crate::rational;
+use crate::settings::{NumberMode, Settings};
+use crate::syntaxtree::{reverse_polish_string_to_tree, SyntaxTreeNode};
+use crate::units;
+use crate::units::Quantity;
 
 
-use crate::tokens::TokenType::{Numeric, Operator, VariableName};
-use crate::tokens::TokenUnion::{FloatOf, StrOf};
+use crate::tokens::TokenType::{Function, Numeric, Operator, VariableName};
+use crate::tokens::TokenUnion::{ComplexOf, DecimalOf, FloatOf, IntegerOf, MatrixOf, QuantityOf, RationalOf, StrOf, VectorOf};
 pub fn solve_reverse_polish_notation(string: &TokenizedString) -> Option<Token> {
+    solve_reverse_polish_notation_with(string, &Settings::default()).ok()
+}
+
+/// Evaluates the expression, failing on a dimension mismatch between quantities, a
+/// shape mismatch between vectors and matrices, or when nothing is left to return.
+pub fn solve_reverse_polish_notation_with(string: &TokenizedString, settings: &Settings) -> Result<Token, String> {
     let str_clone = string.clone();
     let mut stack = vec![];
-    for element in str_clone {
+    let higher_order_calls = higher_order_calls(string);
+    let mut skip_until = None;
+    for (index, element) in str_clone.into_iter().enumerate() {
+        if skip_until.is_some_and(|end| index <= end) {
+            continue;
+        }
+        // The expression argument is evaluated again and again inside the call,
+        // so the whole call is evaluated as a tree instead.
+        if let Some(&end) = higher_order_calls.get(&index) {
+            if settings.number_mode == NumberMode::Decimal {
+                return Err(not_exact(string[end].value_as_string().as_str()));
+            }
+            let tree = reverse_polish_string_to_tree(&string[index..=end].to_vec()).remove(0);
+            stack.push(Token {
+                value: FloatOf(evaluate_tree(&tree, &HashMap::new())?),
+                token_type: Numeric,
+                num_params: 0,
+            });
+            skip_until = Some(end);
+            continue;
+        }
+        // Constants are bound before anything, complex arithmetic included, uses them.
+        if element.token_type == VariableName {
+            if let Some(value) = constant(element.value_as_string().as_str()) {
+                if settings.number_mode == NumberMode::Decimal {
+                    return Err(not_exact(element.value_as_string().as_str()));
+                }
+                stack.push(Token {
+                    value: FloatOf(value),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+                continue;
+            }
+        }
+        if element.token_type == Function {
+            let count = element.num_params.min(stack.len());
+            check_bound(&stack[stack.len() - count..])?;
+            if stack[stack.len() - count..].iter().any(is_quantity) {
+                return Err(format!("Error. {} takes plain numbers, not quantities with units.", element.value_as_string()));
+            }
+            let name = element.value_as_string();
+            if name == "vector" || matrix::FUNCTIONS.contains(&name.as_str()) || stack[stack.len() - count..].iter().any(is_array) {
+                if settings.number_mode == NumberMode::Decimal {
+                    return Err(String::from("Error. Vectors and matrices are not supported in decimal mode."));
+                }
+                let arguments = stack.split_off(stack.len() - count).iter().map(array_value).collect::<Result<Vec<_>, _>>()?;
+                let result = match name.as_str() {
+                    "vector" => matrix::from_elements(&arguments)?,
+                    _ => matrix::apply_function(name.as_str(), &arguments)?,
+                };
+                stack.push(array_token(result));
+                continue;
+            }
+            if settings.complex {
+                if let Some(result) = apply_complex_function(element.value_as_string().as_str(), &stack[stack.len() - count..]) {
+                    stack.truncate(stack.len() - count);
+                    stack.push(complex_token(result));
+                    continue;
+                }
+                if stack[stack.len() - count..].iter().any(is_complex) {
+                    return Err(format!("Error. {} is not defined for complex numbers.", name));
+                }
+            }
+            let integers: option!(Vec<BigInt>) = stack[stack.len() - count..].iter().map(integer_value).collect();
+            if let Some(result) = integers.and_then(|integers| integer::apply_function(element.value_as_string().as_str(), &integers)) {
+                stack.truncate(stack.len() - count);
+                stack.push(Token {
+                    value: IntegerOf(result),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+                continue;
+            }
+            if settings.number_mode == NumberMode::Decimal {
+                let decimals: option!(Vec<Decimal>) = stack[stack.len() - count..].iter().map(decimal_value).collect();
+                let result = match decimals {
+                    Some(decimals) => decimal::apply_function(name.as_str(), &decimals, &settings.decimal)?,
+                    None => None,
+                };
+                let Some(result) = result else {
+                    return Err(if FUNCTIONS.contains(&name.as_str()) { not_exact(name.as_str()) } else { function_error(name.as_str()) });
+                };
+                stack.truncate(stack.len() - count);
+                stack.push(Token {
+                    value: DecimalOf(result),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+                continue;
+            }
+            let arguments: Vec<f64> = stack[stack.len() - count..].iter().map(get_unsafe_float_value).collect();
+            if let Some(result) = apply_function(element.value_as_string().as_str(), &arguments) {
+                stack.truncate(stack.len() - count);
+                stack.push(Token {
+                    value: FloatOf(result),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+                continue;
+            }
+            return Err(function_error(name.as_str()));
+        }
         if element.token_type != Operator {
             stack.push(element.clone());
         } else if element.token_type == Operator {
@@ -18,34 +142,215 @@ pub fn solve_reverse_polish_notation(string: &TokenizedString) -> Option<Token>
             let mut i = 0;
             let num_params = element.num_params;
 
-            while let Some(numeric) = stack.last() {
-                vector.push(stack.pop().unwrap());
+            while let Some(operand) = stack.pop() {
+                vector.push(operand);
                 i += 1;
                 if i >= num_params {
                     break;
                 }
             }
 
-            let new_token = new_token_from_applying(&element, vector);
-
-            if let Some(new_token) = new_token {
-                stack.push(new_token);
+            let name = element.value_as_string();
+            vector.reverse();
+            if let Some(links) = operators::chain_links(name.as_str()) {
+                stack.push(apply_chain(&links, &vector, settings)?);
+                continue;
             }
+            stack.push(apply_registered(name.as_str(), &vector, settings)?);
         }
     }
-    if stack.last().is_none() == true {
-        return None;
+    stack.last().cloned().ok_or_else(|| String::from("Error. Bad expression."))
+}
+
+
+/// Evaluates an operator of `settings.operators` on its operands, given left to right.
+fn apply_registered(name: &str, operands: &[Token], settings: &Settings) -> Result<Token, String> {
+    let evaluate = settings.operators.get(name)
+        .and_then(|operator| operator.evaluate.clone())
+        .ok_or_else(|| format!("Error. Cannot evaluate the {} operator.", name))?;
+    evaluate(operands, settings)
+}
+
+/// Evaluates a chain such as `1 < x <= 10`: 1 if every comparison holds between
+/// neighbouring operands, 0 otherwise.
+fn apply_chain(links: &[&str], operands: &[Token], settings: &Settings) -> Result<Token, String> {
+    if operands.len() != links.len() + 1 {
+        return Err(String::from("Error. Bad expression."));
+    }
+    let mut holds = true;
+    for (link, pair) in links.iter().zip(operands.windows(2)) {
+        holds &= apply_registered(link, pair, settings)?.value.to_f64() != Some(0.0);
+    }
+    Ok(Token {
+        value: IntegerOf(BigInt::from(holds as u8)),
+        token_type: Numeric,
+        num_params: 0,
+    })
+}
+
+/// Evaluates a built-in operator on its operands, given left to right. Every
+/// operator of `OperatorTable::builtin` is evaluated this way.
+pub(crate) fn apply_operator(name: &str, operands: &[Token], settings: &Settings) -> Result<Token, String> {
+    let operator = Token {
+        value: StrOf(String::from(name)),
+        token_type: Operator,
+        num_params: operands.len(),
+    };
+    // Assignments and declarations take the name itself.
+    if name != "=" && name != "declare" {
+        check_bound(operands)?;
     }
-    return Some(stack.last().unwrap().clone());
+    let parameters: Vec<Token> = operands.iter().rev().cloned().collect();
+    if parameters.iter().any(is_array) {
+        return apply_to_arrays(&operator, &parameters);
+    }
+    if parameters.iter().any(is_quantity) {
+        return apply_to_quantities(&operator, &parameters);
+    }
+    let decimals = if parameters.len() == 2 { decimal_operands(&parameters) } else { None };
+    if let Some((a, b)) = decimals {
+        if let Some(result) = decimal::apply(name, &b, &a, &settings.decimal)? {
+            return Ok(Token {
+                value: DecimalOf(result),
+                token_type: Numeric,
+                num_params: 0,
+            });
+        }
+    }
+    let complex = parameters.iter().any(is_complex);
+    let result = new_token_from_applying_with(&operator, parameters, settings).ok_or_else(|| match complex {
+        true => format!("Error. {} is not defined for complex numbers.", name),
+        false => format!("Error. Cannot evaluate the {} operator.", name),
+    })?;
+    if settings.number_mode == NumberMode::Decimal && matches!(result.value, FloatOf(_)) {
+        return Err(not_exact(name));
+    }
+    Ok(result)
 }
 
+/// The error for a result decimal mode would have to compute in binary floating point.
+fn not_exact(name: &str) -> String {
+    format!("Error. {} has no exact value in decimal mode.", name)
+}
 
 fn get_unsafe_float_value(float_tok: &Token) -> f64 {
-    if let FloatOf(f) = float_tok.value {
-        return f.clone();
+    float_tok.value.to_f64().unwrap_or(0.0)
+}
+
+fn is_array(token: &Token) -> bool {
+    matches!(token.value, VectorOf(_) | MatrixOf(_))
+}
+
+fn array_value(token: &Token) -> Result<matrix::Value, String> {
+    match &token.value {
+        VectorOf(v) => Ok(matrix::Value::Vector(v.clone())),
+        MatrixOf(m) => Ok(matrix::Value::Matrix(m.clone())),
+        value => value.to_f64().map(matrix::Value::Scalar).ok_or_else(|| String::from("Error. Bad expression.")),
+    }
+}
+
+fn array_token(value: matrix::Value) -> Token {
+    Token {
+        value: match value {
+            matrix::Value::Scalar(x) => FloatOf(x),
+            matrix::Value::Vector(v) => VectorOf(v),
+            matrix::Value::Matrix(m) => MatrixOf(m),
+        },
+        token_type: Numeric,
+        num_params: 0,
+    }
+}
+
+/// Applies an operator when at least one operand is a vector or a matrix.
+fn apply_to_arrays(o: &Token, parameters: &[Token]) -> Result<Token, String> {
+    let operator = o.value_as_string();
+    let result = match (operator.as_str(), parameters) {
+        ("NEGATE", [a]) => matrix::negate(&array_value(a)?),
+        (_, [b, a]) => matrix::apply(operator.as_str(), &array_value(a)?, &array_value(b)?)?,
+        _ => return Err(format!("Error. {} is not defined for vectors and matrices.", operator)),
+    };
+    Ok(array_token(result))
+}
+
+fn is_complex(token: &Token) -> bool {
+    matches!(token.value, ComplexOf(_))
+}
+
+/// Fails on a variable among the operands, as nothing gives it a value here.
+fn check_bound(operands: &[Token]) -> Result<(), String> {
+    match operands.iter().find(|operand| operand.token_type == VariableName) {
+        Some(variable) => Err(format!("Error. No value for {}.", variable.value_as_string())),
+        None => Ok(()),
+    }
+}
+
+fn is_quantity(token: &Token) -> bool {
+    matches!(token.value, QuantityOf(_))
+}
+
+fn quantity_value(token: &Token) -> Result<Quantity, String> {
+    match &token.value {
+        QuantityOf(q) => Ok(q.clone()),
+        value => value.to_f64().map(Quantity::number).ok_or_else(|| String::from("Error. Bad expression.")),
     }
+}
+
+/// Applies an operator when at least one operand carries a unit. Results whose
+/// units cancel out become plain numbers.
+fn apply_to_quantities(o: &Token, parameters: &[Token]) -> Result<Token, String> {
+    let operator = o.value_as_string();
+    let result = match (operator.as_str(), parameters) {
+        ("NEGATE", [a]) => quantity_value(a)?.scaled(-1.0),
+        (_, [b, a]) => units::apply(operator.as_str(), &quantity_value(a)?, &quantity_value(b)?)?,
+        _ => return Err(format!("Error. {} is not defined for quantities with units.", operator)),
+    };
+    Ok(Token {
+        value: result.as_number()?.map(FloatOf).unwrap_or(QuantityOf(result)),
+        token_type: Numeric,
+        num_params: 0,
+    })
+}
 
-    return 0.0;
+fn complex_value(token: &Token) -> option!(Complex64) {
+    match token.value {
+        ComplexOf(z) => Some(z),
+        _ => token.value.to_f64().map(Complex64::from),
+    }
+}
+
+/// A complex result as a token, collapsed to a float when it is real.
+fn complex_token(z: Complex64) -> Token {
+    Token {
+        value: if z.im == 0.0 { FloatOf(z.re) } else { ComplexOf(z) },
+        token_type: Numeric,
+        num_params: 0,
+    }
+}
+
+/// Complex operands of a binary operator, if at least one is complex or the
+/// operator is `^^` with a negative base and a fractional exponent.
+fn complex_operands(operator: &str, parameters: &[Token]) -> option!((Complex64, Complex64)) {
+    let (b, a) = (complex_value(&parameters[0])?, complex_value(&parameters[1])?);
+    let any_complex = parameters.iter().any(|parameter| matches!(parameter.value, ComplexOf(_)));
+    let real_power_undefined = operator == "^^" && a.re < 0.0 && b.re.fract() != 0.0;
+    if !any_complex && !real_power_undefined {
+        return None;
+    }
+    Some((a, b))
+}
+
+/// Evaluates a built-in over the complex numbers when an argument is complex or
+/// the real built-in has no real answer, such as `sqrt(-4)`.
+fn apply_complex_function(name: &str, arguments: &[Token]) -> option!(Complex64) {
+    let any_complex = arguments.iter().any(|argument| matches!(argument.value, ComplexOf(_)));
+    if !any_complex {
+        let real: Vec<f64> = arguments.iter().map(get_unsafe_float_value).collect();
+        if apply_function(name, &real).is_some_and(|result| !result.is_nan()) {
+            return None;
+        }
+    }
+    let arguments: option!(Vec<Complex64>) = arguments.iter().map(complex_value).collect();
+    complex::apply_function(name, &arguments?)
 }
 
 fn get_unsafe_literal_value(float_tok: &Token) -> String {
@@ -53,20 +358,144 @@ fn get_unsafe_literal_value(float_tok: &Token) -> String {
         return f.clone();
     }
 
-    return String::from("");
+    String::from("")
+}
+
+/// The value of a token holding a whole number of any kind.
+fn integer_value(token: &Token) -> option!(BigInt) {
+    match &token.value {
+        IntegerOf(i) => Some(i.clone()),
+        RationalOf(r) if r.is_integer() => Some(r.to_integer()),
+        DecimalOf(d) => d.to_integer(),
+        FloatOf(f) if f.fract() == 0.0 => BigInt::from_f64(*f),
+        _ => None,
+    }
+}
+
+fn decimal_value(token: &Token) -> option!(Decimal) {
+    match &token.value {
+        DecimalOf(d) => Some(d.clone()),
+        IntegerOf(i) => Some(Decimal::from_integer(i.clone())),
+        _ => None,
+    }
+}
+
+/// Decimal operands of a binary operator, if at least one is a decimal. The other
+/// may be an integer or a float, such as a function result, and is converted.
+fn decimal_operands(parameters: &[Token]) -> option!((Decimal, Decimal)) {
+    let as_decimal = |token: &Token| match &token.value {
+        DecimalOf(d) => Some(d.clone()),
+        IntegerOf(i) => Some(Decimal::from_integer(i.clone())),
+        FloatOf(f) => Decimal::from_f64(*f),
+        _ => None,
+    };
+    if !parameters.iter().any(|parameter| matches!(parameter.value, DecimalOf(_))) {
+        return None;
+    }
+    Some((as_decimal(&parameters[0])?, as_decimal(&parameters[1])?))
+}
+
+/// Rational operands of a binary operator, if at least one is a fraction and the
+/// other a fraction or an integer.
+fn rational_operands(parameters: &[Token]) -> option!((BigRational, BigRational)) {
+    let as_rational = |token: &Token| match &token.value {
+        RationalOf(r) => Some(r.clone()),
+        IntegerOf(i) => Some(BigRational::from_integer(i.clone())),
+        _ => None,
+    };
+    if !parameters.iter().any(|parameter| matches!(parameter.value, RationalOf(_))) {
+        return None;
+    }
+    Some((as_rational(&parameters[0])?, as_rational(&parameters[1])?))
+}
+
+/// Evaluates a comparison or a logical operator. Fractions, decimals and whole
+/// numbers are compared exactly, only floats as floats, and `&&` and `||` treat
+/// non-zero as true.
+fn truth_value(operator: &str, a: &Token, b: &Token) -> option!(bool) {
+    let operands = [a.clone(), b.clone()];
+    let ordering = if let Some((a, b)) = rational_operands(&operands) {
+        Some(a.cmp(&b))
+    } else if let Some((a, b)) = decimal_operands(&operands) {
+        Some(a.cmp(&b))
+    } else {
+        match (integer_value(a), integer_value(b)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.value.to_f64()?.partial_cmp(&b.value.to_f64()?),
+        }
+    };
+    let result = match operator {
+        "==" => ordering == Some(Ordering::Equal),
+        "!=" => ordering != Some(Ordering::Equal),
+        "<" => ordering == Some(Ordering::Less),
+        "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        ">" => ordering == Some(Ordering::Greater),
+        ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        "&&" => a.value.to_f64()? != 0.0 && b.value.to_f64()? != 0.0,
+        "||" => a.value.to_f64()? != 0.0 || b.value.to_f64()? != 0.0,
+        _ => return None,
+    };
+    Some(result)
 }
 
 pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Token) {
+    new_token_from_applying_with(o, parameters, &Settings::default())
+}
+
+pub fn new_token_from_applying_with(o: &Token, parameters: Vec<Token>, settings: &Settings) -> option!(Token) {
     if o.token_type != Operator {
         return None;
     }
     // println!("{:?}, {}", o.value, parameters.len());
     if parameters.len() == 2 {
+        if let (true, StrOf(operator_value)) = (settings.complex, &(o.value)) {
+            if let Some((a, b)) = complex_operands(operator_value, &parameters) {
+                let equal = match operator_value.as_str() {
+                    "==" => Some(a == b),
+                    "!=" => Some(a != b),
+                    _ => None,
+                };
+                if let Some(equal) = equal {
+                    return Some(Token {
+                        value: IntegerOf(BigInt::from(equal as u8)),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+                // The real operators below would read the complex operand as 0.
+                return complex::apply(operator_value, a, b).map(complex_token);
+            }
+        }
+        if let (StrOf(operator_value), Some((a, b))) = (&(o.value), rational_operands(&parameters)) {
+            if let Some(result) = rational::apply(operator_value.as_str(), &b, &a) {
+                return Some(Token {
+                    value: RationalOf(result),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+            }
+        }
+        if let (StrOf(operator_value), IntegerOf(a), IntegerOf(b)) = (&(o.value), &(parameters[0].value), &(parameters[1].value)) {
+            if let Some(result) = integer::apply(operator_value.as_str(), b, a) {
+                return Some(Token {
+                    value: IntegerOf(result),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+            }
+        }
+        if let Some(result) = truth_value(&o.value_as_string(), &parameters[1], &parameters[0]) {
+            return Some(Token {
+                value: IntegerOf(BigInt::from(result as u8)),
+                token_type: Numeric,
+                num_params: 0,
+            });
+        }
         if let StrOf(operator_value) = &(o.value) {
             let asstr = operator_value.as_str();
             match asstr {
                 "+" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     let b = get_unsafe_float_value(parameters.get(1).unwrap());
                     return Some(Token {
                         value: FloatOf(a + b),
@@ -76,7 +505,7 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                 }
 
                 "-" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     let b = get_unsafe_float_value(parameters.get(1).unwrap());
                     return Some(Token {
                         value: FloatOf(b - a),
@@ -86,7 +515,7 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                 }
 
                 "*" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     let b = get_unsafe_float_value(parameters.get(1).unwrap());
                     return Some(Token {
                         value: FloatOf(a * b),
@@ -96,7 +525,7 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                 }
 
                 "/" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     let b = get_unsafe_float_value(parameters.get(1).unwrap());
                     return Some(Token {
                         value: FloatOf(b / a),
@@ -105,8 +534,28 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                     });
                 }
 
+                "//" => {
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
+                    let b = get_unsafe_float_value(parameters.get(1).unwrap());
+                    return Some(Token {
+                        value: FloatOf((b / a).floor()),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+
+                "%" => {
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
+                    let b = get_unsafe_float_value(parameters.get(1).unwrap());
+                    return Some(Token {
+                        value: FloatOf(b - a * (b / a).floor()),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+
                 "^^" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     let b = get_unsafe_float_value(parameters.get(1).unwrap());
                     return Some(Token {
                         value: FloatOf(b.powf(a)),
@@ -116,9 +565,6 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                 }
 
                 "=" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
-                    let b = get_unsafe_literal_value(parameters.get(1).unwrap());
-                    // println!("set value of {:?} to {:?};", b, a);
                     return Some(Token {
                         value: StrOf(String::from("")),
                         token_type: Numeric,
@@ -134,7 +580,31 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
             let asstr = operator_value.as_str();
             match asstr {
                 "NEGATE" => {
-                    let a = get_unsafe_float_value(parameters.get(0).unwrap());
+                    if let ComplexOf(a) = parameters[0].value {
+                        return Some(complex_token(-a));
+                    }
+                    if let RationalOf(a) = &(parameters[0].value) {
+                        return Some(Token {
+                            value: RationalOf(-a),
+                            token_type: Numeric,
+                            num_params: 0,
+                        });
+                    }
+                    if let DecimalOf(a) = &(parameters[0].value) {
+                        return Some(Token {
+                            value: DecimalOf(a.negate()),
+                            token_type: Numeric,
+                            num_params: 0,
+                        });
+                    }
+                    if let IntegerOf(a) = &(parameters[0].value) {
+                        return Some(Token {
+                            value: IntegerOf(-a),
+                            token_type: Numeric,
+                            num_params: 0,
+                        });
+                    }
+                    let a = get_unsafe_float_value(parameters.first().unwrap());
                     return Some(Token {
                         value: FloatOf(-a),
                         token_type: Numeric,
@@ -142,10 +612,36 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
                     });
                 }
 
+                "!" => {
+                    let a = integer_value(parameters.first().unwrap())?;
+                    return Some(Token {
+                        value: IntegerOf(integer::factorial(&a)?),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+
+                "NOT" => {
+                    let a = parameters.first().unwrap().value.to_f64()?;
+                    return Some(Token {
+                        value: IntegerOf(BigInt::from((a == 0.0) as u8)),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+
+                "COMPLEMENT" => {
+                    let a = integer_value(parameters.first().unwrap())?;
+                    return Some(Token {
+                        value: IntegerOf(!a),
+                        token_type: Numeric,
+                        num_params: 0,
+                    });
+                }
+
                 "declare" => {
-                    let a = get_unsafe_literal_value(parameters.get(0).unwrap());
-                    // println!("declared variable {a};");
-                    return return Some(Token {
+                    let a = get_unsafe_literal_value(parameters.first().unwrap());
+                    return Some(Token {
                         value: StrOf(a),
                         token_type: VariableName,
                         num_params: 0,
@@ -157,5 +653,220 @@ pub fn new_token_from_applying(o: &Token, parameters: Vec<Token>) -> option!(Tok
         }
     }
 
-    return None;
-}
\ No newline at end of file
+    None
+}
+
+/// Values of the named constants that need no binding.
+pub fn constant(name: &str) -> option!(f64) {
+    match name {
+        "pi" => Some(consts::PI),
+        "e" => Some(consts::E),
+        "tau" => Some(consts::TAU),
+        _ => None,
+    }
+}
+
+/// Names of the built-ins `apply_function` evaluates over the reals, besides the
+/// integer ones it falls back to.
+const FUNCTIONS: [&str; 20] = [
+    "sin", "cos", "tan", "asin", "acos", "atan", "atan2", "sinh", "cosh", "tanh",
+    "exp", "ln", "log", "sqrt", "abs", "floor", "ceil", "round", "min", "max",
+];
+
+/// The error for a call no built-in could evaluate: an unknown name, or a known
+/// one given arguments it does not take, like `factorial(-1)`.
+fn function_error(name: &str) -> String {
+    let known = [&FUNCTIONS[..], &integer::FUNCTIONS[..], &HIGHER_ORDER_FUNCTIONS[..]].concat();
+    if known.contains(&name) {
+        format!("Error. Bad arguments for {}.", name)
+    } else {
+        format!("Error. Unknown function {}.", name)
+    }
+}
+
+/// Start and end, in the expression, of every call to a higher-order built-in
+/// that is not inside another one, keyed by start.
+fn higher_order_calls(expression: &TokenizedString) -> HashMap<usize, usize> {
+    let mut calls: HashMap<usize, usize> = HashMap::new();
+    for (end, token) in expression.iter().enumerate() {
+        if token.token_type != Function || !HIGHER_ORDER_FUNCTIONS.contains(&token.value_as_string().as_str()) {
+            continue;
+        }
+        // Walks back until every operand the call needs has been accounted for.
+        let mut start = end;
+        let mut needed = token.num_params;
+        while needed > 0 && start > 0 {
+            start -= 1;
+            let operand = &expression[start];
+            needed = needed - 1 + if matches!(operand.token_type, Operator | Function) { operand.num_params } else { 0 };
+        }
+        calls.retain(|&inner, _| inner < start);
+        calls.insert(start, end);
+    }
+    calls
+}
+
+/// Evaluates a built-in function, or `None` if the name or argument count is unknown.
+pub fn apply_function(name: &str, arguments: &[f64]) -> option!(f64) {
+    let result = match (name, arguments) {
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("asin", [x]) => x.asin(),
+        ("acos", [x]) => x.acos(),
+        ("atan", [x]) => x.atan(),
+        ("atan2", [y, x]) => y.atan2(*x),
+        ("sinh", [x]) => x.sinh(),
+        ("cosh", [x]) => x.cosh(),
+        ("tanh", [x]) => x.tanh(),
+        ("exp", [x]) => x.exp(),
+        ("ln", [x]) => x.ln(),
+        ("log", [x]) => x.log10(),
+        ("log", [x, base]) => x.log(*base),
+        ("sqrt", [x]) => x.sqrt(),
+        ("abs", [x]) => x.abs(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.ceil(),
+        ("round", [x]) => x.round(),
+        ("min", [first, rest @ ..]) => rest.iter().fold(*first, |a, b| a.min(*b)),
+        ("max", [first, rest @ ..]) => rest.iter().fold(*first, |a, b| a.max(*b)),
+        _ => {
+            let integers: option!(Vec<BigInt>) = arguments.iter()
+                .map(|x| if x.fract() == 0.0 { BigInt::from_f64(*x) } else { None })
+                .collect();
+            integer::to_f64(&integer::apply_function(name, &integers?)?)
+        }
+    };
+    Some(result)
+}
+
+/// Evaluates a syntax tree with the given variable values. Operators are applied
+/// through `new_token_from_applying`, so results match `solve_reverse_polish_notation`.
+pub fn evaluate_tree(tree: &SyntaxTreeNode, bindings: &HashMap<String, f64>) -> Result<f64, String> {
+    let name = tree.value_as_string();
+    match tree.token_type() {
+        Numeric => tree.as_number().ok_or_else(|| format!("Error. {} is not a number.", name)),
+
+        VariableName => bindings.get(&name).copied()
+            .or_else(|| constant(name.as_str()))
+            .ok_or_else(|| format!("Error. No value for {}.", name)),
+
+        Operator => {
+            let mut parameters = vec![];
+            for child in tree.children().iter().rev() {
+                parameters.push(Token {
+                    value: FloatOf(evaluate_tree(child, bindings)?),
+                    token_type: Numeric,
+                    num_params: 0,
+                });
+            }
+            let operator = Token {
+                value: StrOf(name.clone()),
+                token_type: Operator,
+                num_params: tree.children().len(),
+            };
+            match new_token_from_applying(&operator, parameters) {
+                Some(token) if !matches!(token.value, StrOf(_)) => Ok(get_unsafe_float_value(&token)),
+                _ => Err(format!("Error. Cannot evaluate the {} operator.", name)),
+            }
+        }
+
+        Function if HIGHER_ORDER_FUNCTIONS.contains(&name.as_str()) => evaluate_higher_order(tree, bindings),
+
+        Function => {
+            let mut arguments = vec![];
+            for child in tree.children() {
+                arguments.push(evaluate_tree(child, bindings)?);
+            }
+            apply_function(name.as_str(), &arguments).ok_or_else(|| function_error(name.as_str()))
+        }
+
+        _ => Err(format!("Error. Cannot evaluate {}.", name)),
+    }
+}
+
+/// Built-ins whose first argument is an expression in the variable named by the
+/// second, e.g. `integrate(x ^^ 2, x, 0, 1)`. They re-evaluate that expression
+/// with the variable bound to different values.
+const HIGHER_ORDER_FUNCTIONS: [&str; 4] = ["root", "minimize", "integrate", "nderiv"];
+
+const NUMERIC_TOLERANCE: f64 = 1e-12;
+
+fn evaluate_higher_order(tree: &SyntaxTreeNode, bindings: &HashMap<String, f64>) -> Result<f64, String> {
+    let name = tree.value_as_string();
+    let usage = || match name.as_str() {
+        "nderiv" => format!("Error. Usage: {}(expression, variable, at).", name),
+        _ => format!("Error. Usage: {}(expression, variable, lower, upper).", name),
+    };
+    let (expression, variable, limits) = match tree.children().as_slice() {
+        [expression, variable, limits @ ..] if variable.token_type() == VariableName => {
+            (expression, variable.value_as_string(), limits)
+        }
+        _ => return Err(usage()),
+    };
+    let mut numbers = vec![];
+    for limit in limits {
+        numbers.push(evaluate_tree(limit, bindings)?);
+    }
+
+    let at = |x: f64| {
+        let mut inner = bindings.clone();
+        inner.insert(variable.clone(), x);
+        evaluate_tree(expression, &inner)
+    };
+    // Reports unbound variables and unknown functions instead of returning NaN.
+    at(*numbers.first().ok_or_else(usage)?)?;
+    let f = |x: f64| at(x).unwrap_or(f64::NAN);
+
+    match (name.as_str(), numbers.as_slice()) {
+        ("root", [lower, upper]) => numeric::brent(f, *lower, *upper, NUMERIC_TOLERANCE)
+            .ok_or_else(|| format!("Error. No sign change between {} and {}.", lower, upper)),
+        ("minimize", [lower, upper]) => Ok(numeric::minimize(f, *lower, *upper, 1e-10)),
+        ("integrate", [lower, upper]) => Ok(numeric::integrate(f, *lower, *upper, 1e-10)),
+        ("nderiv", [at]) => Ok(numeric::derivative(f, *at)),
+        _ => Err(usage()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::string_to_rpn;
+
+    /// Evaluates `expression` as `calc` does and prints the result.
+    fn calc(expression: &str, settings: &Settings) -> Result<String, String> {
+        let rpn = string_to_rpn!(expression, settings)?;
+        solve_reverse_polish_notation_with(&rpn, settings).map(|token| token.value_as_string())
+    }
+
+    fn in_mode(number_mode: NumberMode) -> Settings {
+        Settings { number_mode, ..Settings::default() }
+    }
+
+    #[test]
+    fn comparisons_are_exact_in_exact_modes() {
+        let rational = in_mode(NumberMode::Rational);
+        assert_eq!(calc("0.3333333333333333333 == 1/3", &rational), Ok(String::from("0")));
+        assert_eq!(calc("1/3 < 0.34", &rational), Ok(String::from("1")));
+
+        let decimal = in_mode(NumberMode::Decimal);
+        assert_eq!(calc("1.00000000000000000001 > 1", &decimal), Ok(String::from("1")));
+        assert_eq!(calc("1.00000000000000000001 == 1", &decimal), Ok(String::from("0")));
+        assert_eq!(calc("1.10 == 1.1", &decimal), Ok(String::from("1")));
+    }
+
+    #[test]
+    fn decimal_mode_never_uses_floats() {
+        let decimal = in_mode(NumberMode::Decimal);
+        assert_eq!(calc("sqrt(2)", &decimal), Ok(String::from("1.414213562373095048801688724")));
+        assert_eq!(calc("sqrt(6.25)", &decimal), Ok(String::from("2.5")));
+        assert_eq!(calc("2.5 ^^ 2", &decimal), Ok(String::from("6.25")));
+        assert_eq!(calc("max(1.5, 2) + abs(-0.1)", &decimal), Ok(String::from("2.1")));
+        assert_eq!(calc("floor(-1.5) + ceil(-1.5)", &decimal), Ok(String::from("-3")));
+        assert!(calc("2.5 ^^ 0.5", &decimal).is_err());
+        assert!(calc("1 / 0", &decimal).is_err());
+        assert!(calc("sin(1)", &decimal).is_err());
+        assert!(calc("pi", &decimal).is_err());
+        assert!(calc("1e100001", &decimal).is_err());
+    }
+}
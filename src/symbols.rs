@@ -0,0 +1,41 @@
+pub const MINUS: &str = "−";
+
+/// Greek letter names, as spelled in LaTeX, with the letter each stands for.
+const GREEK_LETTERS: [(&str, char); 37] = [
+    ("alpha", 'α'), ("beta", 'β'), ("gamma", 'γ'), ("delta", 'δ'), ("epsilon", 'ε'), ("varepsilon", 'ε'),
+    ("zeta", 'ζ'), ("eta", 'η'), ("theta", 'θ'), ("vartheta", 'ϑ'), ("iota", 'ι'), ("kappa", 'κ'),
+    ("lambda", 'λ'), ("mu", 'μ'), ("nu", 'ν'), ("xi", 'ξ'), ("pi", 'π'), ("rho", 'ρ'), ("sigma", 'σ'),
+    ("tau", 'τ'), ("upsilon", 'υ'), ("phi", 'φ'), ("varphi", 'ϕ'), ("chi", 'χ'), ("psi", 'ψ'),
+    ("omega", 'ω'), ("Gamma", 'Γ'), ("Delta", 'Δ'), ("Theta", 'Θ'), ("Lambda", 'Λ'), ("Xi", 'Ξ'),
+    ("Pi", 'Π'), ("Sigma", 'Σ'), ("Upsilon", 'Υ'), ("Phi", 'Φ'), ("Psi", 'Ψ'), ("Omega", 'Ω'),
+];
+
+/// Binary operators typeset differently from how they are typed: the operator,
+/// its unicode symbol and its LaTeX command.
+const BINARY_SYMBOLS: [(&str, &str, &str); 13] = [
+    ("-", MINUS, "-"),
+    ("*", "·", "\\cdot"),
+    ("==", "=", "="),
+    ("!=", "≠", "\\neq"),
+    ("<=", "≤", "\\leq"),
+    (">=", "≥", "\\geq"),
+    ("&&", "∧", "\\land"),
+    ("||", "∨", "\\lor"),
+    ("^", "⊕", "\\oplus"),
+    ("&", "&", "\\mathbin{\\&}"),
+    ("|", "|", "\\mid"),
+    ("%", "%", "\\bmod"),
+    ("//", "//", "\\mathbin{//}"),
+];
+
+pub fn greek_letter(name: &str) -> Option<char> {
+    GREEK_LETTERS.iter().find(|(greek_name, _)| *greek_name == name).map(|(_, letter)| *letter)
+}
+
+pub fn unicode_symbol(operator: &str) -> &str {
+    BINARY_SYMBOLS.iter().find(|(name, _, _)| *name == operator).map_or(operator, |(_, unicode, _)| unicode)
+}
+
+pub fn latex_symbol(operator: &str) -> &str {
+    BINARY_SYMBOLS.iter().find(|(name, _, _)| *name == operator).map_or(operator, |(_, _, latex)| latex)
+}
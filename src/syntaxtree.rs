@@ -1,12 +1,87 @@
 #[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
 pub struct SyntaxTreeNode {
     value: TokenUnion,
+    token_type: TokenType,
     children: Vec<SyntaxTreeNode>,
 }
 
 impl SyntaxTreeNode {
-    pub fn add_child(&mut self, child: SyntaxTreeNode) {
-        self.children.push(child);
+    pub fn new(value: TokenUnion, token_type: TokenType, children: Vec<SyntaxTreeNode>) -> SyntaxTreeNode {
+        SyntaxTreeNode { value, token_type, children }
+    }
+
+    pub fn number(value: f64) -> SyntaxTreeNode {
+        SyntaxTreeNode::new(FloatOf(value), Numeric, vec![])
+    }
+
+    pub fn variable(name: &str) -> SyntaxTreeNode {
+        SyntaxTreeNode::new(StrOf(String::from(name)), VariableName, vec![])
+    }
+
+    pub fn operator(operator: &str, operands: Vec<SyntaxTreeNode>) -> SyntaxTreeNode {
+        SyntaxTreeNode::new(StrOf(String::from(operator)), Operator, operands)
+    }
+
+    pub fn function(name: &str, arguments: Vec<SyntaxTreeNode>) -> SyntaxTreeNode {
+        SyntaxTreeNode::new(StrOf(String::from(name)), Function, arguments)
+    }
+
+    /// The literal's value, if this node is a number.
+    pub fn as_number(&self) -> Option<f64> {
+        match self.token_type {
+            Numeric => self.value.to_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn is_operator(&self, operator: &str) -> bool {
+        self.token_type == Operator && self.value == StrOf(String::from(operator))
+    }
+
+    /// Whether the variable occurs anywhere in the tree.
+    pub fn contains_variable(&self, name: &str) -> bool {
+        (self.token_type == VariableName && self.value_as_string() == name)
+            || self.children.iter().any(|child| child.contains_variable(name))
+    }
+
+    /// A copy of the tree with every occurrence of the variable replaced.
+    pub fn substitute(&self, name: &str, replacement: &SyntaxTreeNode) -> SyntaxTreeNode {
+        if self.token_type == VariableName && self.value_as_string() == name {
+            return replacement.clone();
+        }
+        let children = self.children.iter().map(|child| child.substitute(name, replacement)).collect();
+        SyntaxTreeNode::new(self.value.clone(), self.token_type, children)
+    }
+
+    /// Names of the variables in the tree, in order of first occurrence.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = vec![];
+        if self.token_type == VariableName {
+            names.push(self.value_as_string());
+        }
+        for child in &self.children {
+            for name in child.variables() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    pub fn value(&self) -> &TokenUnion {
+        &self.value
+    }
+
+    pub fn token_type(&self) -> TokenType {
+        self.token_type
+    }
+
+    /// Operands in source order, e.g. `[a, b]` for `a - b`.
+    pub fn children(&self) -> &Vec<SyntaxTreeNode> {
+        &self.children
     }
 
     pub fn value_as_string(&self) -> String {
@@ -14,8 +89,22 @@ impl SyntaxTreeNode {
             return s.clone();
         } else if let FloatOf(f) = self.value {
             return format!("{}", f);
+        } else if let RationalOf(r) = &(self.value) {
+            return r.to_string();
+        } else if let DecimalOf(d) = &(self.value) {
+            return d.to_string();
+        } else if let IntegerOf(i) = &(self.value) {
+            return i.to_string();
+        } else if let ComplexOf(z) = &(self.value) {
+            return complex::format(z);
+        } else if let QuantityOf(q) = &(self.value) {
+            return q.to_string();
+        } else if let VectorOf(v) = &(self.value) {
+            return matrix::format_vector(v);
+        } else if let MatrixOf(m) = &(self.value) {
+            return matrix::format_matrix(m);
         }
-        return String::from("");
+        String::from("")
     }
 
     pub fn print(&self) {
@@ -25,61 +114,187 @@ impl SyntaxTreeNode {
     fn pretty_print(&self, level: usize) {
         let string = "\t".repeat(level);
         println!("{}- Name: {}", string, self.value_as_string());
-        let vec_children: Vec<String> = self.children.iter().map(|c| c.value_as_string()).collect();
         for child in &self.children {
             child.pretty_print(level + 1);
         }
     }
+
+    /// Binding strength used when printing, from `operators`. Operands that are not
+    /// operators never need parentheses, with the exception of negative literals which
+    /// read like a `NEGATE` application and fractions which read like a division.
+    pub(crate) fn precedence(&self, operators: &OperatorTable) -> isize {
+        match self.token_type {
+            Operator => operators.precedence(self.value_as_string().as_str()),
+            Numeric if matches!(&self.value, RationalOf(r) if !r.is_integer()) => operators.precedence("/"),
+            Numeric if matches!(self.value, FloatOf(f) if f.is_sign_negative()) => operators.precedence("NEGATE"),
+            Numeric if matches!(&self.value, RationalOf(r) if r.is_negative()) => operators.precedence("NEGATE"),
+            Numeric if matches!(&self.value, DecimalOf(d) if d.is_negative()) => operators.precedence("NEGATE"),
+            Numeric if matches!(&self.value, IntegerOf(i) if i.is_negative()) => operators.precedence("NEGATE"),
+            Numeric if matches!(self.value, ComplexOf(z) if z.re != 0.0) => operators.precedence("+"),
+            Numeric if matches!(self.value, ComplexOf(z) if z.im.is_sign_negative()) => operators.precedence("NEGATE"),
+            Numeric if matches!(self.value, QuantityOf(_)) => operators.precedence("*"),
+            _ => isize::MAX,
+        }
+    }
+
+    /// The tree as infix text for a parser using `operators`; `Display` prints it
+    /// with the built-in operators.
+    pub fn display_with<'a>(&'a self, operators: &'a OperatorTable) -> Infix<'a> {
+        Infix { tree: self, operators }
+    }
 }
 
-use std::fmt::{Display, Formatter};
-use crate::tokens::{Token, TokenizedString, TokenType, TokenUnion};
-use crate::tokens::TokenType::{Function, Numeric, Operator};
-use crate::tokens::TokenUnion::{FloatOf, StrOf};
+pub struct Infix<'a> {
+    tree: &'a SyntaxTreeNode,
+    operators: &'a OperatorTable,
+}
+
+impl Infix<'_> {
+    fn is_prefix_application(&self) -> bool {
+        let tree = self.tree;
+        tree.token_type == Operator && tree.children.len() == 1 && !self.operators.is_postfix(tree.value_as_string().as_str())
+    }
+
+    fn needs_parentheses_as_operand(&self, parent_precedence: isize, tie_needs_parentheses: bool) -> bool {
+        let precedence = self.tree.precedence(self.operators);
+        precedence < parent_precedence || (precedence == parent_precedence && tie_needs_parentheses)
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>, parenthesize: bool) -> std::fmt::Result {
+        if parenthesize {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+/// Prints the tree as infix text that `Token::tokenize_string_with` parses back into
+/// the same tree, using parentheses only where precedence or associativity
+/// requires them.
+impl Display for Infix<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (tree, operators) = (self.tree, self.operators);
+        let name = tree.value_as_string();
+        let child = |index: usize| tree.children[index].display_with(operators);
+        match tree.token_type {
+            Operator if chain_links(name.as_str()).is_some_and(|links| links.len() + 1 == tree.children.len()) => {
+                // `a < b <= c`, where every operand binds tighter than the comparisons.
+                let precedence = tree.precedence(operators);
+                for (index, link) in chain_links(name.as_str()).unwrap().into_iter().enumerate() {
+                    let operand = child(index);
+                    operand.fmt_operand(f, operand.needs_parentheses_as_operand(precedence, true))?;
+                    write!(f, " {} ", link)?;
+                }
+                let last = child(tree.children.len() - 1);
+                last.fmt_operand(f, last.needs_parentheses_as_operand(precedence, true))
+            }
 
+            Operator if tree.children.len() == 2 => {
+                let precedence = tree.precedence(operators);
+                let associativity = operators.associativity(name.as_str());
+                let (left, right) = (child(0), child(1));
+                left.fmt_operand(f, left.needs_parentheses_as_operand(precedence, associativity != Left))?;
+                write!(f, " {} ", name)?;
+                // A prefix operator on the right takes its own operand before the
+                // parent's, so `x ^^ -3` needs no parentheses.
+                right.fmt_operand(f, !right.is_prefix_application() && right.needs_parentheses_as_operand(precedence, associativity != Right))
+            }
 
-macro_rules! replace_in_array {
-    ($vector: expr => $start: expr, $end: expr; $new_vector: expr) => {
-        {
-            let mut ret_val = vec![];
-            for token in 0..(usize::from($start)) {
-                ret_val.push($vector.get(token).unwrap().clone());
+            Operator if tree.children.len() == 1 && operators.is_postfix(name.as_str()) => {
+                let operand = child(0);
+                operand.fmt_operand(f, operand.needs_parentheses_as_operand(tree.precedence(operators), true))?;
+                write!(f, "{}", name)
             }
 
-            for token in $new_vector {
-                ret_val.push(token.clone());
+            Operator if tree.children.len() == 1 => {
+                let operand = child(0);
+                let symbol = operators.spelling(name.as_str());
+                if symbol.chars().all(char::is_alphabetic) {
+                    write!(f, "{} ", symbol)?;
+                } else {
+                    write!(f, "{}", symbol)?;
+                }
+                // `--x` would lex as a single operator, so operands that also start with a
+                // prefix operator are wrapped as well.
+                operand.fmt_operand(f, operand.needs_parentheses_as_operand(tree.precedence(operators), true))
             }
 
-            for token2 in (usize::from($end)+1)..($vector.len()) {
-                ret_val.push($vector.get(token2).unwrap().clone());
+            Function => {
+                let (open, close) = if name == "vector" { ("[", "]") } else { ("(", ")") };
+                if name != "vector" {
+                    write!(f, "{}", name)?;
+                }
+                write!(f, "{}", open)?;
+                for index in 0..tree.children.len() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", child(index))?;
+                }
+                write!(f, "{}", close)
             }
 
-            ret_val
+            _ => write!(f, "{}", name),
         }
-    };
+    }
 }
 
-pub fn polish_notation_to_normal(tokenized_string: &TokenizedString) -> TokenizedString {
-    let string = detokenize_tokens(tokenized_string.clone());
-    return string;
+impl Display for SyntaxTreeNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.display_with(OperatorTable::builtin()).fmt(f)
+    }
 }
 
-trait Reversable {
-    fn reversed(&self) -> TokenizedString;
+use std::fmt::{Display, Formatter};
+use num_traits::Signed;
+use crate::operators::{chain_links, OperatorTable};
+use crate::settings::Settings;
+use crate::tokens::{string_to_rpn, Token, TokenizedString, TokenType, TokenUnion};
+use crate::tokens::Associativity::{Left, Right};
+use crate::tokens::TokenType::{Function, Numeric, Operator, VariableName};
+use crate::complex;
+use crate::matrix;
+use crate::tokens::TokenUnion::{ComplexOf, DecimalOf, FloatOf, IntegerOf, MatrixOf, QuantityOf, RationalOf, StrOf, VectorOf};
+
+
+/// Prints each statement on a line of its own.
+pub fn polish_notation_to_normal(tokenized_string: &TokenizedString, operators: &OperatorTable) -> String {
+    reverse_polish_string_to_tree(tokenized_string).iter()
+        .map(|root| root.display_with(operators).to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
-impl Reversable for TokenizedString {
-    fn reversed(&self) -> TokenizedString {
-        let mut x = self.clone();
-        x.reverse();
-        return x;
+/// Parses infix text into a single expression tree.
+pub fn string_to_tree(string: &str) -> Result<SyntaxTreeNode, String> {
+    string_to_tree_with(string, &Settings::default())
+}
+
+pub fn string_to_tree_with(string: &str, settings: &Settings) -> Result<SyntaxTreeNode, String> {
+    let rpn = string_to_rpn!(string, settings)?;
+    let mut depth = 0;
+    for token in &rpn {
+        if token.token_type == Operator || token.token_type == Function {
+            if depth < token.num_params {
+                return Err(format!("Error. Missing operand for {}.", token.value_as_string()));
+            }
+            depth -= token.num_params;
+        }
+        depth += 1;
+    }
+    if depth != 1 {
+        return Err(String::from("Error. Bad expression."));
     }
+    Ok(reverse_polish_string_to_tree(&rpn).remove(0))
 }
+
 pub(crate) fn reverse_polish_string_to_tree(tokenized_string: &TokenizedString) -> Vec<SyntaxTreeNode> {
     let mut stack: Vec<SyntaxTreeNode> = vec![];
-    for (index, element) in tokenized_string.iter().enumerate() {
+    for element in tokenized_string.iter() {
         let mut node = SyntaxTreeNode {
-            value: StrOf(element.value_as_string()),
+            value: element.value.clone(),
+            token_type: element.token_type,
             children: vec![]
         };
 
@@ -89,6 +304,7 @@ pub(crate) fn reverse_polish_string_to_tree(tokenized_string: &TokenizedString)
             for _ in 0..num_par {
                 children.push(stack.pop().unwrap());
             }
+            children.reverse();
 
             node.children = children;
 
@@ -96,70 +312,66 @@ pub(crate) fn reverse_polish_string_to_tree(tokenized_string: &TokenizedString)
         stack.push(node);
     }
 
-    return stack;
+    stack
 }
 
-fn detokenize_tokens(tokenized_string: TokenizedString) -> TokenizedString {
-    let filtered: Vec<&Token> = tokenized_string.iter().filter(|toke|
-        {
-            return toke.token_type == Operator || toke.token_type == Function;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prints `source` and checks that the text is `expected` and parses back into
+    /// the same tree.
+    fn assert_round_trip(source: &str, expected: &str, settings: &Settings) {
+        let tree = string_to_tree_with(source, settings).unwrap();
+        let printed = tree.display_with(&settings.operators).to_string();
+        assert_eq!(printed, expected, "printing {}", source);
+        assert_eq!(string_to_tree_with(printed.as_str(), settings), Ok(tree), "reparsing {}", printed);
+    }
+
+    #[test]
+    fn prefix_and_postfix_operators_round_trip() {
+        let settings = Settings::default();
+        for (source, expected) in [
+            ("-x ^^ 2", "-x ^^ 2"),
+            ("(-x) ^^ 2", "(-x) ^^ 2"),
+            ("x ^^ -3", "x ^^ -3"),
+            ("--x", "-(-x)"),
+            ("-(a + b)", "-(a + b)"),
+            ("!a && b", "!a && b"),
+            ("~(a & b)", "~(a & b)"),
+            ("(a + b)!", "(a + b)!"),
+            ("(-x)!", "(-x)!"),
+            ("f(a, -b) * [1, 2]", "f(a, -b) * [1, 2]"),
+        ] {
+            assert_round_trip(source, expected, &settings);
         }
-    ).collect();
-
-    if filtered.is_empty() {
-        return tokenized_string;
-    } else {
-        for (index, element) in tokenized_string.iter().enumerate() {
-            match element.token_type {
-                Operator => {
-                    if (element.num_params == 2) {
-                        let token = Token {
-                            value: StrOf(format!("({} {} {})", tokenized_string.get(index - 2).unwrap().value_as_string(),
-                                                 element.value_as_string(), tokenized_string.get(index - 1).unwrap().value_as_string())),
-                            token_type: Numeric,
-                            num_params: 0,
-                        };
-                        let new_tokenized = replace_in_array!(tokenized_string => index - element.num_params, index; vec![token]);
-                        return detokenize_tokens(new_tokenized);
-                    } else {
-                        let token = Token {
-                            value: StrOf(format!("({} {})",
-                                                 element.value_as_string(), tokenized_string.get(index - 1).unwrap().value_as_string())),
-                            token_type: Numeric,
-                            num_params: 0,
-                        };
-                        let new_tokenized = replace_in_array!(tokenized_string => index - element.num_params, index; vec![token]);
-                        return detokenize_tokens(new_tokenized);
-                    }
-                }
+    }
 
-                Function => {
-                    let mut index_copy = index;
-                    let mut value = String::from("[");
-                    value += element.value_as_string().as_str();
-                    value += "; ";
-                    while index_copy != index - element.num_params {
-                        index_copy -= 1;
-                        value = value + tokenized_string.get(index_copy).unwrap().value_as_string().as_str();
-                        if index_copy != index - element.num_params {
-                            value = value + "; ";
-                        }
-                    }
-                    value += "]";
-
-                    // println!("value: {}", value);
-                    let token = Token {
-                        value: StrOf(value),
-                        token_type: Numeric,
-                        num_params: 0,
-                    };
-                    let new_tokenized = replace_in_array!(tokenized_string => index - element.num_params, index; vec![token]);
-                    return detokenize_tokens(new_tokenized);
-                }
-                _ => {}
-            }
+    #[test]
+    fn associativity_round_trips() {
+        let settings = Settings::default();
+        for (source, expected) in [
+            ("a ^^ b ^^ c", "a ^^ b ^^ c"),
+            ("(a ^^ b) ^^ c", "(a ^^ b) ^^ c"),
+            ("(a - b) - c", "a - b - c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("a / (b * c)", "a / (b * c)"),
+            ("(a < b) == c", "(a < b) == c"),
+        ] {
+            assert_round_trip(source, expected, &settings);
         }
-        return tokenized_string;
     }
-}
 
+    #[test]
+    fn chained_comparisons_round_trip() {
+        let settings = Settings { chained_comparisons: true, ..Settings::default() };
+        for (source, expected) in [
+            ("1 < x <= 10", "1 < x <= 10"),
+            ("a == b == c", "a == b == c"),
+            ("(a < b) < c < d", "(a < b) < c < d"),
+            ("0 <= x + 1 < (y == z)", "0 <= x + 1 < (y == z)"),
+        ] {
+            assert_round_trip(source, expected, &settings);
+        }
+    }
+}
@@ -1,7 +1,16 @@
 #[derive(Debug)]
+#[derive(PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum TokenUnion {
     StrOf(String),
     FloatOf(f64),
+    RationalOf(BigRational),
+    DecimalOf(Decimal),
+    IntegerOf(BigInt),
+    ComplexOf(Complex64),
+    QuantityOf(Quantity),
+    VectorOf(Vec<f64>),
+    MatrixOf(Vec<Vec<f64>>),
 }
 
 impl Clone for TokenUnion {
@@ -9,10 +18,41 @@ impl Clone for TokenUnion {
         if let StrOf(s) = self {
             return StrOf(s.clone());
         } else if let FloatOf(f) = self {
-            return FloatOf(f.clone());
+            return FloatOf(*f);
+        } else if let RationalOf(r) = self {
+            return RationalOf(r.clone());
+        } else if let DecimalOf(d) = self {
+            return DecimalOf(d.clone());
+        } else if let IntegerOf(i) = self {
+            return IntegerOf(i.clone());
+        } else if let ComplexOf(z) = self {
+            return ComplexOf(*z);
+        } else if let QuantityOf(q) = self {
+            return QuantityOf(q.clone());
+        } else if let VectorOf(v) = self {
+            return VectorOf(v.clone());
+        } else if let MatrixOf(m) = self {
+            return MatrixOf(m.clone());
         }
 
-        return StrOf(String::from("nan"));
+        StrOf(String::from("nan"))
+    }
+}
+
+impl TokenUnion {
+    /// The value as a real number, or `None` for names, non-real complex numbers,
+    /// quantities with units, vectors and matrices.
+    pub(crate) fn to_f64(&self) -> Option<f64> {
+        match self {
+            StrOf(_) => None,
+            FloatOf(f) => Some(*f),
+            RationalOf(r) => Some(rational::to_f64(r)),
+            DecimalOf(d) => Some(d.to_f64()),
+            IntegerOf(i) => Some(integer::to_f64(i)),
+            ComplexOf(z) => (z.im == 0.0).then_some(z.re),
+            QuantityOf(q) => q.as_number().ok().flatten(),
+            VectorOf(_) | MatrixOf(_) => None,
+        }
     }
 }
 
@@ -32,7 +72,9 @@ pub enum TokenType {
     ClosedCurly,
     Comma,
     ParamName,
-    VariableName
+    VariableName,
+    /// A line break ending a statement.
+    Separator
 }
 
 #[derive(Clone)]
@@ -48,99 +90,44 @@ macro_rules! option {
     };
 }
 
-fn remove_spaces_from_str(string: &String) -> String {
-    let mut sw = 1;
-    let mut strs = String::from("");
-    for c in string.chars() {
-        if c != ' ' {
-            sw = 0;
-        }
-        if sw == 0 {
-            strs = strs + c.to_string().as_str();
-        }
-    }
-
-    while strs.chars().last() == Some(' ') {
-        strs.pop();
-    }
-
-    strs
-}
-
-macro_rules! empty_in {
-    ($v: ident, $vv: ident => $($a: ident; $bb: expr), *) => {
-        $(
-            if remove_spaces_from_str(&$a) != String::from("") && remove_spaces_from_str(&$a) != String::from(" ") {
-                $v.push(remove_spaces_from_str(&$a));
-                $vv.push($bb);
-            }
-            $a = String::from("");
-        )*
-    };
-}
-macro_rules! dictionary {
-    ($ ($key : expr => $value : expr), * ) => {
-        {
-            use std::collections::HashMap;
-            let mut dict = HashMap::new();
-            $(
-                dict.insert($key, $value);
-            )*
-            dict
-        }
-    };
-
-    ($t: ty, $k: ty) => {
-        {
-            use std::collections::HashMap;
-            let mut dict: HashMap<$t, $k> = HashMap::new();
-            dict
-        }
-    }
-}
-
-macro_rules! tokenize {
-    ($string: expr) => {
-        {
-            Token::tokenize_string($string)
-        }
-    }
-}
-
-
 macro_rules! string_to_rpn {
     ($string: expr) => {
         {
-            let tokens = Token::tokenize_string($string);
-            Token::reverse_polish_notation(tokens)
+            Token::tokenize_string($string)
+                .and_then(|tokens| Token::reverse_polish_notation(tokens).map_err(String::from))
         }
-    }
-}
+    };
 
-macro_rules! matches_regex {
-    ($string: expr, $reg: expr) => {
+    ($string: expr, $settings: expr) => {
         {
-            let regex = Regex::new($reg).unwrap();
-            regex.is_match($string)
+            Token::tokenize_string_with($string, $settings)
+                .and_then(|tokens| Token::reverse_polish_notation_with(tokens, $settings).map_err(String::from))
         }
     }
 }
 
-
-
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
-use std::ops::Range;
 pub(crate) use option;
-pub(crate) use dictionary;
-use regex::{Error, Regex};
-pub(crate) use tokenize;
 pub(crate) use string_to_rpn;
+use num_bigint::BigInt;
+use unicode_ident::{is_xid_continue, is_xid_start};
+use num_complex::Complex64;
+use num_rational::BigRational;
+use crate::complex;
+use crate::decimal::Decimal;
+use crate::integer;
+use crate::matrix;
 use crate::operators;
+use crate::operators::OperatorTable;
+use crate::rational;
+use crate::settings::{ImplicitMultiplication, NumberMode, Settings};
+use crate::units;
+use crate::units::Quantity;
 // use crate::operators::Operators;
-use crate::tokens::Associativity::{Left, Right};
-use crate::tokens::TokenType::{Function, ClosedBracket, ClosedCurly, ClosedPar, Comma, Numeric, OpenBracket, OpenCurly, OpenPar, Operator, ParamName, VariableName};
-use crate::tokens::TokenUnion::{FloatOf, StrOf};
+use crate::tokens::Associativity::{Left, NoAsoc};
+use crate::tokens::TokenType::{Function, ClosedBracket, ClosedCurly, ClosedPar, Comma, Numeric, OpenBracket, OpenCurly, OpenPar, Operator, ParamName, Separator, VariableName};
+use crate::tokens::TokenUnion::{ComplexOf, DecimalOf, FloatOf, IntegerOf, MatrixOf, QuantityOf, RationalOf, StrOf, VectorOf};
 
 impl Debug for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -150,6 +137,44 @@ impl Debug for Token {
 
 pub type TokenizedString = Vec<Token>;
 
+/// Operator symbols by character, so that `Token::lex` can read the longest
+/// registered symbol at a position: `**-` is `*` then `-`, while `<=` stays whole.
+#[derive(Default)]
+struct SymbolTrie {
+    children: HashMap<char, SymbolTrie>,
+    terminal: bool,
+}
+
+impl SymbolTrie {
+    fn new(symbols: &[&str]) -> SymbolTrie {
+        let mut trie = SymbolTrie::default();
+        for symbol in symbols {
+            let mut node = &mut trie;
+            for character in symbol.chars() {
+                node = node.children.entry(character).or_default();
+            }
+            node.terminal = true;
+        }
+        trie
+    }
+
+    /// Length in bytes of the longest symbol `text` starts with.
+    fn longest_match(&self, text: &str) -> option!(usize) {
+        let mut node = self;
+        let mut longest = None;
+        for (index, character) in text.char_indices() {
+            match node.children.get(&character) {
+                Some(child) => node = child,
+                None => break,
+            }
+            if node.terminal {
+                longest = Some(index + character.len_utf8());
+            }
+        }
+        longest
+    }
+}
+
 #[derive(Copy, Clone)]
 #[derive(PartialEq)]
 pub enum Associativity {
@@ -166,62 +191,47 @@ impl<'lifetime> Token {
             return s.clone();
         } else if let FloatOf(f) = self.value {
             return format!("{}", f);
+        } else if let RationalOf(r) = &(self.value) {
+            return r.to_string();
+        } else if let DecimalOf(d) = &(self.value) {
+            return d.to_string();
+        } else if let IntegerOf(i) = &(self.value) {
+            return i.to_string();
+        } else if let ComplexOf(z) = &(self.value) {
+            return complex::format(z);
+        } else if let QuantityOf(q) = &(self.value) {
+            return q.to_string();
+        } else if let VectorOf(v) = &(self.value) {
+            return matrix::format_vector(v);
+        } else if let MatrixOf(m) = &(self.value) {
+            return matrix::format_matrix(m);
         }
-        return String::from("");
+        String::from("")
     }
-    pub fn tokenize_string(string: &'lifetime str) -> TokenizedString {
-        let mut number: String = String::from("");
-        let mut variable: String = String::from("");
-        let mut operator: String = String::from("");
-
-        let mut vector = vec![];
-        let mut v2 = vec![];
 
-        for (index, character) in string.chars().enumerate() {
-            let next_char = string.chars().nth(index + 1);
-            let mut prev_char = Some(' ');
-            if index > 0 {
-                prev_char = string.chars().nth(index - 1);
-            }
+    pub fn tokenize_string(string: &'lifetime str) -> Result<TokenizedString, String> {
+        Self::tokenize_string_with(string, &Settings::default())
+    }
 
-            if matches_regex!(variable.as_str(), r"\s*declare\s*") {
-                empty_in!(vector, v2 => variable; Operator, number; Numeric, operator; Operator);
-            } else if character == '-' && next_char.is_none() == false && prev_char.is_none() == false &&
-                next_char.unwrap() != ' ' && (prev_char.unwrap() == ' ' || prev_char.unwrap() == '(') {
-                empty_in!(vector, v2 => variable; Function, number; Numeric, operator; Operator);
-                vector.push(String::from("NEGATE"));
-                v2.push(Operator);
-            } else if character == ',' {
-                empty_in!(vector, v2 => variable; Function, number; Numeric, operator; Operator);
-                vector.push(String::from(","));
-                v2.push(Comma);
-            } else if character.is_numeric() || character == '.' {
-                number = number + character.to_string().as_str();
-                empty_in!(vector, v2 => variable; Function, operator; Operator);
-            } else if character.is_alphabetic() || character == ' ' {
-                variable = variable + character.to_string().as_str();
-                empty_in!(vector, v2 => number; Numeric, operator; Operator);
-            } else if character == '(' || character == ')' {
-                empty_in!(vector, v2 => operator; Operator);
-                operator = operator + character.to_string().as_str();
-                let typ = if character == '(' { OpenPar } else { ClosedPar };
-                empty_in!(vector, v2 => variable; Function, number; Numeric, operator; typ);
-            } else if character == '[' || character == ']' {
-                empty_in!(vector, v2 => operator; Operator);
-                operator = operator + character.to_string().as_str();
-                let typ = if character == '[' { OpenBracket } else { ClosedBracket };
-                empty_in!(vector, v2 => variable; Function, number; Numeric, operator; typ);
-            } else if character == '{' || character == '}' {
-                empty_in!(vector, v2 => operator; Operator);
-                operator = operator + character.to_string().as_str();
-                let typ = if character == '{' { OpenCurly } else { ClosedCurly };
-                empty_in!(vector, v2 => variable; Function, number; Numeric, operator; typ);
-            } else {
-                operator = operator + character.to_string().as_str();
-                empty_in!(vector, v2 => variable; Function, number; Numeric);
-            }
+    /// The value of a numeric literal in the given number mode.
+    fn number_value(literal: &str, settings: &Settings) -> Result<TokenUnion, String> {
+        match settings.number_mode {
+            NumberMode::Rational => if let Some(r) = rational::parse(literal) {
+                return Ok(RationalOf(r));
+            },
+            NumberMode::Decimal => return Decimal::parse(literal).map(DecimalOf).ok_or_else(|| format!("Error. The decimal {} is out of range.", literal)),
+            NumberMode::Float => if let Some(i) = integer::parse(literal) {
+                return Ok(IntegerOf(i));
+            },
         }
-        empty_in!(vector, v2 => variable; Function, number; Numeric, operator; Operator);
+        Ok(FloatOf(literal.parse().unwrap()))
+    }
+
+    pub fn tokenize_string_with(string: &'lifetime str, settings: &Settings) -> Result<TokenizedString, String> {
+        let lexemes = Self::lex(string, &settings.operators)?;
+        let vector: Vec<&String> = lexemes.iter().map(|(text, _)| text).collect();
+        let v2: Vec<TokenType> = lexemes.iter().map(|(_, typ)| *typ).collect();
+        let get_num_pars = |operator: &str| Some(settings.operators.arity(operator));
 
         let mut vfin: Vec<Token> = vec![];
         for i in 0..vector.len() {
@@ -230,35 +240,374 @@ impl<'lifetime> Token {
             if let Some(typ) = typ {
                 if let Some(string) = string {
                     if let Some(str) = vector.get(i + 1) {
-                        if str == ":" {
-                            let opt = Self::get_num_pars(string);
+                        if *str == ":" {
+                            let opt = get_num_pars(string);
                             vfin.push(Token {
-                                value: if *typ == Numeric { FloatOf(string.clone().parse().unwrap()) } else { StrOf(string.clone()) },
+                                value: if *typ == Numeric { Self::number_value(string, settings)? } else { StrOf(String::clone(string)) },
                                 token_type: ParamName,
-                                num_params: if opt.is_none() { 0 } else { opt.unwrap() },
+                                num_params: opt.unwrap_or(0),
                             });
                             continue;
-                        } else if str != "(" && (*typ) == Function {
+                        } else if *str != "(" && (*typ) == Function {
                             vfin.push(Token {
-                                value: StrOf(string.clone()),
+                                value: StrOf(String::clone(string)),
                                 token_type: VariableName,
                                 num_params: 0,
                             });
                             continue;
                         }
+                    } else if *typ == Function {
+                        vfin.push(Token {
+                            value: StrOf(String::clone(string)),
+                            token_type: VariableName,
+                            num_params: 0,
+                        });
+                        continue;
                     }
-                    let opt = Self::get_num_pars(string);
+                    let opt = get_num_pars(string);
                     vfin.push(Token {
-                        value: if *typ == Numeric { FloatOf(string.clone().parse().unwrap()) } else { StrOf(string.clone()) },
+                        value: if *typ == Numeric { Self::number_value(string, settings)? } else { StrOf(String::clone(string)) },
                         token_type: *typ,
-                        num_params: if opt.is_none() { 0 } else { opt.unwrap() },
+                        num_params: opt.unwrap_or(0),
                     })
                 }
             }
         }
 
 
-        return vfin;
+        if settings.units {
+            vfin = Self::unit_quantities(vfin)?;
+        }
+        if settings.complex {
+            vfin = Self::imaginary_literals(vfin);
+        }
+        let product = match settings.implicit_multiplication {
+            ImplicitMultiplication::Off => None,
+            ImplicitMultiplication::SameAsProduct => Some("*"),
+            ImplicitMultiplication::Tighter => Some(operators::IMPLICIT_PRODUCT),
+        };
+        if let Some(product) = product {
+            vfin = Self::implicit_products(vfin, product, &settings.operators);
+        }
+        Self::check_adjacent_operands(&vfin, &settings.operators)?;
+        Ok(vfin)
+    }
+
+    /// Splits the input into lexemes in a single pass, typing identifiers as
+    /// `Function` for now and keywords of the operator table as `Operator`.
+    /// Whitespace and comments only separate lexemes: `#` comments out the rest of
+    /// the line, as `//` is floor division, and `/* ... */` comments out a block.
+    /// Operators are read by longest match against the table's symbols, and any
+    /// other character is an error. Whether a symbol is a prefix operator depends
+    /// on the lexeme before it.
+    fn lex(string: &str, operators: &OperatorTable) -> Result<Vec<(String, TokenType)>, String> {
+        let keywords = operators.keywords();
+        let symbols = SymbolTrie::new(&operators.symbols());
+        let mut lexemes = vec![];
+        // Number of lexemes read before each line break.
+        let mut line_breaks = vec![];
+        let mut characters = string.char_indices().peekable();
+
+        while let Some((index, character)) = characters.next() {
+            let next = characters.peek().map(|&(_, next)| next);
+            if character == '#' {
+                while characters.next_if(|&(_, next)| next != '\n').is_some() {}
+                continue;
+            }
+            if character == '/' && next == Some('*') {
+                let end = string[index + 2..].find("*/")
+                    .ok_or_else(|| format!("Error. Unterminated comment at {}.", Self::location(string, index)))?;
+                let end = index + 2 + end + 2;
+                while characters.next_if(|&(next_index, _)| next_index < end).is_some() {}
+                continue;
+            }
+            if character == '\n' {
+                line_breaks.push(lexemes.len());
+                continue;
+            }
+            let punctuation = match character {
+                ',' => Some((",", Comma)),
+                '(' => Some(("(", OpenPar)),
+                ')' => Some((")", ClosedPar)),
+                '[' => Some(("[", OpenBracket)),
+                ']' => Some(("]", ClosedBracket)),
+                '{' => Some(("{", OpenCurly)),
+                '}' => Some(("}", ClosedCurly)),
+                _ => None,
+            };
+            if let Some((text, typ)) = punctuation {
+                lexemes.push((String::from(text), typ));
+                continue;
+            }
+            if character.is_whitespace() {
+                continue;
+            }
+
+            if character.is_ascii_digit() || (character == '.' && next.is_some_and(|next| next.is_ascii_digit())) {
+                let (end, literal) = Self::scan_number(string, index)?;
+                while characters.next_if(|&(next_index, _)| next_index < end).is_some() {}
+                lexemes.push((literal, Numeric));
+            } else if is_xid_start(character) || character == '_' {
+                let mut end = index + character.len_utf8();
+                while let Some((next_index, next)) = characters.next_if(|&(_, next)| is_xid_continue(next)) {
+                    end = next_index + next.len_utf8();
+                }
+                let text = &string[index..end];
+                let typ = if keywords.contains(&text) { Operator } else { Function };
+                lexemes.push((String::from(text), typ));
+            } else {
+                let end = index + symbols.longest_match(&string[index..])
+                    .ok_or_else(|| format!("Error. Unknown symbol {} at {}.", character, Self::location(string, index)))?;
+                while characters.next_if(|&(next_index, _)| next_index < end).is_some() {}
+                if let Some(symbol) = Self::sign_operator(&string[index..end], lexemes.last(), operators) {
+                    lexemes.push((String::from(symbol), Operator));
+                }
+            }
+        }
+        if line_breaks.is_empty() {
+            return Ok(lexemes);
+        }
+        Ok(Self::separate_statements(lexemes, &line_breaks, operators))
+    }
+
+    /// Ends a statement at a line break outside brackets, as long as the lexeme
+    /// before it ends an operand and the one after it starts one. Other line
+    /// breaks, such as after an operator, continue the statement.
+    fn separate_statements(lexemes: Vec<(String, TokenType)>, line_breaks: &[usize], operators: &OperatorTable) -> Vec<(String, TokenType)> {
+        let ends_operand = |(text, typ): &(String, TokenType)| match typ {
+            Numeric | Function | ClosedPar | ClosedBracket | ClosedCurly => true,
+            Operator => operators.is_postfix(text),
+            _ => false,
+        };
+        // Read after an operand, a symbol such as `~` that is only a prefix
+        // operator has not been named as one yet.
+        let prefix_only = |text: &str| operators.get(text).is_none().then(|| operators.prefix_named(text)).flatten();
+        let starts_operand = |(text, typ): &(String, TokenType)| match typ {
+            Numeric | Function | OpenPar | OpenBracket | OpenCurly => true,
+            Operator => operators.is_prefix(text) || prefix_only(text).is_some(),
+            _ => false,
+        };
+        let mut line_breaks = line_breaks.iter().peekable();
+        let mut depth = 0usize;
+        let mut separated = vec![];
+        for (index, mut lexeme) in lexemes.into_iter().enumerate() {
+            let mut after_line_break = false;
+            while line_breaks.next_if(|&&line_break| line_break <= index).is_some() {
+                after_line_break = true;
+            }
+            if after_line_break && depth == 0 && separated.last().is_some_and(ends_operand) && starts_operand(&lexeme) {
+                separated.push((String::from("\n"), Separator));
+                if let Some(name) = prefix_only(&lexeme.0) {
+                    lexeme.0 = String::from(name);
+                }
+            }
+            match lexeme.1 {
+                OpenPar | OpenBracket | OpenCurly => depth += 1,
+                ClosedPar | ClosedBracket | ClosedCurly => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            separated.push(lexeme);
+        }
+        separated
+    }
+
+    /// Line and column of the character at byte `index`, both counted from 1, as in
+    /// `line 2, column 5`.
+    fn location(string: &str, index: usize) -> String {
+        let before = &string[..index];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        format!("line {}, column {}", line, column)
+    }
+
+    /// Names an operator symbol by what comes before it. After an operand it is the
+    /// binary or postfix operator; anywhere else it is the prefix operator typed that
+    /// way, if there is one, and a prefix `+` is dropped.
+    fn sign_operator<'a>(symbol: &'a str, previous: Option<&(String, TokenType)>, operators: &'a OperatorTable) -> option!(&'a str) {
+        let after_operand = match previous {
+            Some((_, Numeric | Function | ClosedPar | ClosedBracket | ClosedCurly)) => true,
+            Some((text, Operator)) => operators.is_postfix(text),
+            _ => false,
+        };
+        if after_operand {
+            return Some(symbol);
+        }
+        if symbol == "+" {
+            return None;
+        }
+        Some(operators.prefix_named(symbol).unwrap_or(symbol))
+    }
+
+    /// Reads the numeric literal starting at byte `start`: decimals such as `1.5`,
+    /// `.5`, `5.` or `6.02E23`, with `_` allowed between digits, and integers with a
+    /// `0x`, `0b` or `0o` prefix. Gives the byte after the literal and its text with
+    /// the underscores removed and prefixed integers written in decimal.
+    fn scan_number(string: &str, start: usize) -> Result<(usize, String), String> {
+        let bytes = string.as_bytes();
+        let malformed = |end: usize| format!("Error. Malformed number {} at {}.", &string[start..end], Self::location(string, start));
+        let underscores_between_digits = |digits: &[u8]| digits.iter().enumerate().all(|(index, byte)| {
+            *byte != b'_' || (index > 0 && digits[index - 1].is_ascii_alphanumeric()
+                && digits.get(index + 1).is_some_and(u8::is_ascii_alphanumeric))
+        });
+        let mut end = start;
+
+        let radix = match (bytes[start], bytes.get(start + 1)) {
+            (b'0', Some(b'x' | b'X')) => 16,
+            (b'0', Some(b'b' | b'B')) => 2,
+            (b'0', Some(b'o' | b'O')) => 8,
+            _ => 10,
+        };
+        if radix != 10 {
+            end = start + 2;
+            while bytes.get(end).is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_') {
+                end += 1;
+            }
+            let digits = &bytes[start + 2..end];
+            if !underscores_between_digits(digits) {
+                return Err(malformed(end));
+            }
+            let digits: Vec<u8> = digits.iter().copied().filter(|byte| *byte != b'_').collect();
+            return BigInt::parse_bytes(&digits, radix)
+                .map(|value| (end, value.to_string()))
+                .ok_or_else(|| malformed(end));
+        }
+
+        while bytes.get(end).is_some_and(|byte| byte.is_ascii_digit() || matches!(byte, b'_' | b'.')) {
+            end += 1;
+        }
+        let mantissa = &bytes[start..end];
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let digits = if matches!(bytes.get(end + 1), Some(b'+' | b'-')) { end + 2 } else { end + 1 };
+            if bytes.get(digits).is_some_and(u8::is_ascii_digit) {
+                end = digits;
+                while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+                    end += 1;
+                }
+            }
+        }
+        let points = mantissa.iter().filter(|byte| **byte == b'.').count();
+        if points > 1 || !underscores_between_digits(mantissa) {
+            return Err(malformed(end.max(start + mantissa.len())));
+        }
+        Ok((end, string[start..end].replace('_', "")))
+    }
+
+    /// Fails on two operands in a row, such as `foo bar`, `2 x` or `(1)(2)`, where
+    /// it is not clear which operator was meant, and on a prefix operator left over
+    /// after an operand, as in `x declare`.
+    fn check_adjacent_operands(tokens: &TokenizedString, operators: &OperatorTable) -> Result<(), String> {
+        for pair in tokens.windows(2) {
+            if !Self::ends_operand(&pair[0], operators) {
+                continue;
+            }
+            if Self::starts_operand(&pair[1]) {
+                return Err(format!(
+                    "Error. Missing operator between {} and {}.",
+                    pair[0].value_as_string(),
+                    pair[1].value_as_string(),
+                ));
+            }
+            let name = pair[1].value_as_string();
+            if pair[1].token_type == Operator && (operators.is_prefix(name.as_str()) || (operators.get(name.as_str()).is_none() && operators.prefix_named(name.as_str()).is_some())) {
+                return Err(format!("Error. Unexpected {} after {}.", operators.spelling(name.as_str()), pair[0].value_as_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `token` can be the last token of an operand: a number, a name, a
+    /// closing bracket or a postfix operator.
+    fn ends_operand(token: &Token, operators: &OperatorTable) -> bool {
+        match token.token_type {
+            Numeric | VariableName | ClosedPar | ClosedBracket => true,
+            Operator => operators.is_postfix(token.value_as_string().as_str()),
+            _ => false,
+        }
+    }
+
+    /// Whether `token` can be the first token of an operand: a number, a name, a
+    /// function call or an opening bracket.
+    fn starts_operand(token: &Token) -> bool {
+        matches!(token.token_type, Numeric | VariableName | Function | OpenPar | OpenBracket)
+    }
+
+    /// Inserts the `product` operator between an operand, a closing bracket or a
+    /// postfix operator and a following operand, function call or opening
+    /// bracket, as in `2x`, `3(a + b)`, `(a + 1)(a - 1)` or `2 pi`.
+    fn implicit_products(tokens: TokenizedString, product: &str, operators: &OperatorTable) -> TokenizedString {
+        let mut multiplied: TokenizedString = vec![];
+        for token in tokens {
+            if multiplied.last().is_some_and(|last| Self::ends_operand(last, operators)) && Self::starts_operand(&token) {
+                multiplied.push(Token {
+                    value: StrOf(String::from(product)),
+                    token_type: Operator,
+                    num_params: 2,
+                });
+            }
+            multiplied.push(token);
+        }
+        multiplied
+    }
+
+    /// Reads unit symbols as quantities, merging a number right before a unit, as in
+    /// `5 km`, into one quantity, and reads `in` and `to` as the conversion operator.
+    fn unit_quantities(tokens: TokenizedString) -> Result<TokenizedString, String> {
+        let mut merged: TokenizedString = vec![];
+        for token in tokens {
+            let name = token.value_as_string();
+            if token.token_type != VariableName || !units::is_unit(name.as_str()) {
+                let is_conversion = token.token_type == VariableName && units::CONVERSION_OPERATORS.contains(&name.as_str());
+                merged.push(if is_conversion {
+                    Token {
+                        value: token.value,
+                        token_type: Operator,
+                        num_params: 2,
+                    }
+                } else {
+                    token
+                });
+                continue;
+            }
+            let count = match merged.last() {
+                Some(previous) if previous.token_type == Numeric => previous.value.to_f64(),
+                _ => None,
+            };
+            if count.is_some() {
+                merged.pop();
+            }
+            merged.push(Token {
+                value: QuantityOf(Quantity::unit(name.as_str())?.scaled(count.unwrap_or(1.0))),
+                token_type: Numeric,
+                num_params: 0,
+            });
+        }
+        Ok(merged)
+    }
+
+    /// Merges a number followed by `i` or `j`, as in `4i`, into an imaginary literal
+    /// and reads a lone `i` or `j` as the imaginary unit.
+    fn imaginary_literals(tokens: TokenizedString) -> TokenizedString {
+        let mut merged: TokenizedString = vec![];
+        for token in tokens {
+            let is_unit = token.token_type == VariableName && matches!(token.value_as_string().as_str(), "i" | "j");
+            if !is_unit {
+                merged.push(token);
+                continue;
+            }
+            let coefficient = match merged.last() {
+                Some(previous) if previous.token_type == Numeric => previous.value.to_f64(),
+                _ => None,
+            };
+            if coefficient.is_some() {
+                merged.pop();
+            }
+            merged.push(Token {
+                value: ComplexOf(Complex64::new(0.0, coefficient.unwrap_or(1.0))),
+                token_type: Numeric,
+                num_params: 0,
+            });
+        }
+        merged
     }
 
     pub fn tokens_to_string(tokenized_string: &TokenizedString) -> String {
@@ -269,15 +618,43 @@ impl<'lifetime> Token {
                 string += " ";
             } else if let FloatOf(f) = &(elem.value) {
                 string += format!("{} ", f).as_str();
+            } else if let RationalOf(r) = &(elem.value) {
+                string += format!("{} ", r).as_str();
+            } else if let DecimalOf(d) = &(elem.value) {
+                string += format!("{} ", d).as_str();
+            } else if let IntegerOf(i) = &(elem.value) {
+                string += format!("{} ", i).as_str();
+            } else if let ComplexOf(z) = &(elem.value) {
+                string += format!("{} ", complex::format(z)).as_str();
+            } else if let QuantityOf(q) = &(elem.value) {
+                string += format!("{} ", q).as_str();
+            } else if let VectorOf(v) = &(elem.value) {
+                string += format!("{} ", matrix::format_vector(v)).as_str();
+            } else if let MatrixOf(m) = &(elem.value) {
+                string += format!("{} ", matrix::format_matrix(m)).as_str();
             }
         }
-        return string;
+        string
     }
 
     pub fn reverse_polish_notation(tokenized_string: TokenizedString) -> Result<TokenizedString, &'lifetime str> {
+        Self::reverse_polish_notation_with(tokenized_string, &Settings::default())
+    }
+
+    /// Orders the tokens for evaluation with the shunting-yard algorithm. Chained
+    /// non-associative operators, such as `a == b == c`, are an error unless
+    /// `settings.chained_comparisons` is on, in which case they become a single
+    /// chain token taking every operand once.
+    pub fn reverse_polish_notation_with(tokenized_string: TokenizedString, settings: &Settings) -> Result<TokenizedString, &'lifetime str> {
+        let operators = &settings.operators;
         let mut output_queue: TokenizedString = vec![];
         let mut operator_stack: TokenizedString = vec![];
+        // One entry per open parenthesis: the number of commas seen so far if the
+        // parenthesis opened a function call, `None` for plain grouping.
+        let mut argument_counts: Vec<Option<usize>> = vec![];
+        let mut previous_type: Option<TokenType> = None;
         for token in tokenized_string {
+            let token_type = token.token_type;
             match token.token_type {
                 Numeric | VariableName => {
                     output_queue.push(token);
@@ -292,25 +669,49 @@ impl<'lifetime> Token {
                 }
 
                 Comma => {
+                    if matches!(previous_type, Some(Comma) | Some(OpenPar) | Some(OpenBracket)) {
+                        return Err("Error. Missing element before ','.");
+                    }
                     while let Some(o2) = operator_stack.last() {
-                        if o2.token_type != OpenPar {
+                        if o2.token_type != OpenPar && o2.token_type != OpenBracket {
                             output_queue.push(operator_stack.pop().clone().unwrap());
                         } else {
                             break;
                         }
                     }
+                    if let Some(Some(commas)) = argument_counts.last_mut() {
+                        *commas += 1;
+                    }
+                }
+
+                // A prefix operator has no left operand to finish first.
+                Operator if operators.is_prefix(token.value_as_string().as_str()) => {
+                    operator_stack.push(token);
                 }
 
                 Operator => {
+                    let mut token = token;
                     while let Some(o2) = operator_stack.last() {
                         if !(o2.token_type == Operator || o2.token_type == ClosedPar) {
                             break;
                         }
 
-                        let precedence_o1 = Self::get_precedence_group_t(&token).unwrap();
-                        let precedence_o2 = Self::get_precedence_group_t(o2).unwrap();
-                        let assoc_o1 = Self::get_associativity_t(&token).unwrap();
+                        let precedence_o1 = Self::get_precedence_group_t(&token, operators).unwrap();
+                        let precedence_o2 = Self::get_precedence_group_t(o2, operators).unwrap();
+                        let assoc_o1 = Self::get_associativity_t(&token, operators).unwrap();
 
+                        if precedence_o2 == precedence_o1 && assoc_o1 == NoAsoc && o2.token_type == Operator {
+                            if !settings.chained_comparisons {
+                                return Err("Error. Comparisons and other non-associative operators cannot be chained without parentheses.");
+                            }
+                            let chain = operator_stack.pop().unwrap();
+                            token = Token {
+                                value: StrOf(operators::chain_name(&chain.value_as_string(), &token.value_as_string())),
+                                token_type: Operator,
+                                num_params: chain.num_params + 1,
+                            };
+                            break;
+                        }
                         if precedence_o2 > precedence_o1 || (precedence_o2 == precedence_o1 && assoc_o1 == Left) {
                             output_queue.push(o2.clone());
                             operator_stack.pop();
@@ -322,10 +723,15 @@ impl<'lifetime> Token {
                 }
 
                 OpenPar => {
+                    let opens_call = operator_stack.last().map(|tp| tp.token_type == Function) == Some(true);
+                    argument_counts.push(if opens_call { Some(0) } else { None });
                     operator_stack.push(token);
                 }
 
                 ClosedPar => {
+                    if previous_type == Some(Comma) {
+                        return Err("Error. Missing element after ','.");
+                    }
                     if operator_stack.last().is_none() {
                         return Err("Error. Bad expression.");
                     }
@@ -347,10 +753,15 @@ impl<'lifetime> Token {
                             if there is a function token at the top of the operator stack, then:
                             pop the function from the operator stack into the output queue
                              */
+                            let commas = argument_counts.pop().flatten();
                             let tp = operator_stack.last();
                             if let Some(tp) = tp {
                                 if tp.token_type == Function {
-                                    output_queue.push(operator_stack.pop().unwrap().clone());
+                                    let mut function = operator_stack.pop().unwrap();
+                                    if let Some(commas) = commas {
+                                        function.num_params = if previous_type == Some(OpenPar) { 0 } else { commas + 1 };
+                                    }
+                                    output_queue.push(function);
                                 }
                             }
                         } else {
@@ -361,49 +772,179 @@ impl<'lifetime> Token {
                     }
                 }
 
+                // A statement ends with all of its operators applied.
+                Separator => {
+                    while let Some(o2) = operator_stack.pop() {
+                        if o2.token_type != Operator && o2.token_type != Function {
+                            return Err("Error. Bad expression.");
+                        }
+                        output_queue.push(o2);
+                    }
+                    argument_counts.clear();
+                }
+
+                // `[a, b]` is a call to the `vector` built-in.
+                OpenBracket => {
+                    argument_counts.push(Some(0));
+                    operator_stack.push(token);
+                }
+
+                ClosedBracket => {
+                    if previous_type == Some(Comma) {
+                        return Err("Error. Missing element after ','.");
+                    }
+                    while let Some(o2) = operator_stack.last() {
+                        if o2.token_type == OpenBracket {
+                            break;
+                        }
+                        output_queue.push(o2.clone());
+                        operator_stack.pop();
+                    }
+                    if operator_stack.pop().map(|open| open.token_type) != Some(OpenBracket) {
+                        return Err("Error. Bad expression.");
+                    }
+                    let commas = argument_counts.pop().flatten().unwrap_or(0);
+                    output_queue.push(Token {
+                        value: StrOf(String::from("vector")),
+                        token_type: Function,
+                        num_params: if previous_type == Some(OpenBracket) { 0 } else { commas + 1 },
+                    });
+                }
+
                 _ => {}
             }
+            previous_type = Some(token_type);
         }
 
-        while operator_stack.is_empty() == false {
-            output_queue.push(operator_stack.pop().unwrap());
+        while let Some(operator) = operator_stack.pop() {
+            output_queue.push(operator);
+        }
+        for token in output_queue.iter_mut() {
+            if token.token_type == Operator && token.value_as_string() == operators::IMPLICIT_PRODUCT {
+                token.value = StrOf(String::from("*"));
+            }
         }
 
-        return Ok(output_queue);
+        Ok(output_queue)
     }
 
-    fn get_num_pars(operator: &str) -> option!(usize) {
-        if let Some(t) = operators::num_pars().get(operator) {
-            return Some(t.clone());
+    fn get_precedence_group_t(operator: &Token, operators: &OperatorTable) -> option!(isize) {
+        if let StrOf(val) = &(operator.value) {
+            return Some(operators.precedence(val.as_str()));
         }
-        return Some(2);
+        Some(90)
     }
 
-    fn get_associativity(operator: &str) -> option!(Associativity) {
-        if let Some(t) = operators::associativity().get(operator) {
-            return Some(t.clone());
+    fn get_associativity_t(operator: &Token, operators: &OperatorTable) -> option!(Associativity) {
+        if let StrOf(val) = &(operator.value) {
+            return Some(operators.associativity(val.as_str()));
         }
-        return Some(Left);
+        Some(Left)
     }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn get_precedence_group_t(operator: &Token) -> option!(isize) {
-        if let StrOf(val) = &(operator.value) {
-            let v2: &str = val.as_str();
-            if let Some(t) = operators::precedence_groups().get(v2) {
-                return Some(t.clone());
-            }
+    #[test]
+    fn lists_reject_empty_elements() {
+        for source in ["[1,]", "[,]", "[1,,2]", "[,1]", "max(1,)", "max(,1)"] {
+            assert!(string_to_rpn!(source).is_err(), "parsing {}", source);
         }
-        return Some(90);
+        assert_eq!(string_to_rpn!("[]").map(|rpn| Token::tokens_to_string(&rpn)), Ok(String::from("vector ")));
     }
 
-    fn get_associativity_t(operator: &Token) -> option!(Associativity) {
-        if let StrOf(val) = &(operator.value) {
-            let v2: &str = val.as_str();
-            if let Some(t) = operators::associativity().get(v2) {
-                return Some(t.clone());
-            }
+    fn rpn(source: &str) -> Result<String, String> {
+        string_to_rpn!(source).map(|rpn| Token::tokens_to_string(&rpn))
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(rpn("1 + 2 # three"), Ok(String::from("1 2 + ")));
+        assert_eq!(rpn("1 /* two */ + 2"), Ok(String::from("1 2 + ")));
+        assert_eq!(rpn("6 // 4"), Ok(String::from("6 4 // ")));
+    }
+
+    #[test]
+    fn line_breaks_separate_statements() {
+        let types = |source: &str| Token::tokenize_string(source).unwrap().iter().map(|token| token.token_type).collect::<Vec<TokenType>>();
+        assert_eq!(types("x = 1\nx"), vec![VariableName, Operator, Numeric, Separator, VariableName]);
+        // Inside brackets or after an operator, a line break only continues the statement.
+        assert_eq!(types("(1\n+ 2)"), vec![OpenPar, Numeric, Operator, Numeric, ClosedPar]);
+        assert_eq!(types("1 +\n2"), vec![Numeric, Operator, Numeric]);
+        assert_eq!(rpn("x = 1\ny = x + 1"), Ok(String::from("x 1 = y x 1 + = ")));
+    }
+
+    #[test]
+    fn leftover_tokens_are_rejected() {
+        assert_eq!(rpn("x declare"), Err(String::from("Error. Unexpected declare after x.")));
+        assert_eq!(rpn("a ~ b"), Err(String::from("Error. Unexpected ~ after a.")));
+        assert_eq!(rpn("x y"), Err(String::from("Error. Missing operator between x and y.")));
+    }
+
+    fn lexemes(source: &str) -> Vec<(String, TokenType)> {
+        Token::lex(source, OperatorTable::builtin()).unwrap()
+    }
+
+    #[test]
+    fn lexer_reads_each_lexeme_once() {
+        assert_eq!(lexemes("max(x1, 2.5) >= y_2"), vec![
+            (String::from("max"), Function), (String::from("("), OpenPar), (String::from("x1"), Function),
+            (String::from(","), Comma), (String::from("2.5"), Numeric), (String::from(")"), ClosedPar),
+            (String::from(">="), Operator), (String::from("y_2"), Function),
+        ]);
+        assert_eq!(rpn("1 +\n  @"), Err(String::from("Error. Unknown symbol @ at line 2, column 3.")));
+        let long = vec!["x1 * 2.5"; 10_000].join(" + ");
+        assert_eq!(lexemes(long.as_str()).len(), 10_000 * 4 - 1);
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let numbers = |source: &str| lexemes(source).into_iter().map(|(text, _)| text).collect::<Vec<String>>();
+        assert_eq!(numbers("1e-3 6.02E23 .5 5."), vec!["1e-3", "6.02E23", ".5", "5."]);
+        assert_eq!(numbers("1_000 0x1F 0b101 0o17"), vec!["1000", "31", "5", "15"]);
+        assert_eq!(rpn("1e-3 * 1_000"), Ok(String::from("0.001 1000 * ")));
+        for malformed in ["1.2.3", "0x", "1__0"] {
+            assert_eq!(rpn(malformed), Err(format!("Error. Malformed number {} at line 1, column 1.", malformed)));
         }
-        return Some(Left);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unary_operators_follow_from_the_previous_token() {
+        assert_eq!(rpn("-3 + 2"), Ok(String::from("3 NEGATE 2 + ")));
+        assert_eq!(rpn("2 - -3"), Ok(String::from("2 3 NEGATE - ")));
+        assert_eq!(rpn("2 -3"), Ok(String::from("2 3 - ")));
+        assert_eq!(rpn("-(x)"), Ok(String::from("x NEGATE ")));
+        assert_eq!(rpn("+3"), Ok(String::from("3 ")));
+        assert_eq!(rpn("!a"), Ok(String::from("a NOT ")));
+        assert_eq!(rpn("~1"), Ok(String::from("1 COMPLEMENT ")));
+        assert_eq!(rpn("3!"), Ok(String::from("3 ! ")));
+    }
+
+    #[test]
+    fn operators_are_matched_longest_first() {
+        let texts = |source: &str| lexemes(source).into_iter().map(|(text, _)| text).collect::<Vec<String>>();
+        assert_eq!(texts("2*-3"), vec!["2", "*", "NEGATE", "3"]);
+        assert_eq!(texts("a<-b"), vec!["a", "<", "NEGATE", "b"]);
+        assert_eq!(texts("a<=b"), vec!["a", "<=", "b"]);
+        assert_eq!(texts("6//4^^2"), vec!["6", "//", "4", "^^", "2"]);
+        assert_eq!(rpn("2*-3"), Ok(String::from("2 3 NEGATE * ")));
+        assert_eq!(rpn("a ; b"), Err(String::from("Error. Unknown symbol ; at line 1, column 3.")));
+    }
+
+    #[test]
+    fn implicit_products() {
+        let implicit = |source: &str, implicit_multiplication| {
+            let settings = Settings { implicit_multiplication, ..Settings::default() };
+            string_to_rpn!(source, &settings).map(|rpn| Token::tokens_to_string(&rpn))
+        };
+        let same = ImplicitMultiplication::SameAsProduct;
+        assert_eq!(implicit("2x", same), Ok(String::from("2 x * ")));
+        assert_eq!(implicit("(a+1)(a-1)", same), Ok(String::from("a 1 + a 1 - * ")));
+        assert_eq!(implicit("3 sin(x)", same), Ok(String::from("3 x sin * ")));
+        assert_eq!(implicit("1/2x", same), Ok(String::from("1 2 / x * ")));
+        assert_eq!(implicit("1/2x", ImplicitMultiplication::Tighter), Ok(String::from("1 2 x * / ")));
+        assert_eq!(rpn("2x"), Err(String::from("Error. Missing operator between 2 and x.")));
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::{Display, Formatter};
+
+/// Exponents of the SI base dimensions: length, mass, time, current,
+/// temperature, amount of substance and luminous intensity.
+type Dimension = [i32; 7];
+
+const LENGTH: Dimension = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dimension = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimension = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: Dimension = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dimension = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dimension = [0, 0, 0, 0, 0, 0, 1];
+const AREA: Dimension = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dimension = [3, 0, 0, 0, 0, 0, 0];
+const FREQUENCY: Dimension = [0, 0, -1, 0, 0, 0, 0];
+const SPEED: Dimension = [1, 0, -1, 0, 0, 0, 0];
+const FORCE: Dimension = [1, 1, -2, 0, 0, 0, 0];
+const PRESSURE: Dimension = [-1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dimension = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dimension = [2, 1, -3, 0, 0, 0, 0];
+const CHARGE: Dimension = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dimension = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dimension = [2, 1, -3, -2, 0, 0, 0];
+
+/// Known unit symbols with their size in SI base units.
+const UNITS: [(&str, f64, Dimension); 39] = [
+    ("m", 1.0, LENGTH),
+    ("km", 1e3, LENGTH),
+    ("cm", 1e-2, LENGTH),
+    ("mm", 1e-3, LENGTH),
+    ("um", 1e-6, LENGTH),
+    ("nm", 1e-9, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("yd", 0.9144, LENGTH),
+    ("mi", 1609.344, LENGTH),
+    ("inch", 0.0254, LENGTH),
+    ("kg", 1.0, MASS),
+    ("g", 1e-3, MASS),
+    ("mg", 1e-6, MASS),
+    ("t", 1e3, MASS),
+    ("lb", 0.45359237, MASS),
+    ("s", 1.0, TIME),
+    ("ms", 1e-3, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3600.0, TIME),
+    ("day", 86400.0, TIME),
+    ("A", 1.0, CURRENT),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("cd", 1.0, LUMINOSITY),
+    ("ha", 1e4, AREA),
+    ("L", 1e-3, VOLUME),
+    ("mL", 1e-6, VOLUME),
+    ("Hz", 1.0, FREQUENCY),
+    ("mph", 0.44704, SPEED),
+    ("N", 1.0, FORCE),
+    ("Pa", 1.0, PRESSURE),
+    ("bar", 1e5, PRESSURE),
+    ("J", 1.0, ENERGY),
+    ("kWh", 3.6e6, ENERGY),
+    ("W", 1.0, POWER),
+    ("kW", 1e3, POWER),
+    ("C", 1.0, CHARGE),
+    ("V", 1.0, VOLTAGE),
+    ("Ohm", 1.0, RESISTANCE),
+];
+
+/// Operators converting the quantity on the left into the unit on the right.
+pub const CONVERSION_OPERATORS: [&str; 2] = ["in", "to"];
+
+pub fn is_unit(symbol: &str) -> bool {
+    UNITS.iter().any(|(name, _, _)| *name == symbol)
+}
+
+fn lookup(symbol: &str) -> Result<(f64, Dimension), String> {
+    UNITS.iter()
+        .find(|(name, _, _)| *name == symbol)
+        .map(|(_, factor, dimension)| (*factor, *dimension))
+        .ok_or_else(|| format!("Error. Unknown unit {}.", symbol))
+}
+
+/// A number together with the unit it is measured in. The unit is kept as the
+/// user wrote it, e.g. `km/h`, rather than reduced to SI base units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    /// Unit symbols with their exponents, in order of first appearance.
+    unit: Vec<(String, i32)>,
+}
+
+impl Quantity {
+    /// One of the named unit, such as `km`.
+    pub fn unit(symbol: &str) -> Result<Quantity, String> {
+        lookup(symbol)?;
+        Ok(Quantity { value: 1.0, unit: vec![(String::from(symbol), 1)] })
+    }
+
+    pub fn number(value: f64) -> Quantity {
+        Quantity { value, unit: vec![] }
+    }
+
+    pub fn scaled(&self, factor: f64) -> Quantity {
+        Quantity { value: self.value * factor, unit: self.unit.clone() }
+    }
+
+    /// The plain number, if the units cancel out.
+    pub fn as_number(&self) -> Result<Option<f64>, String> {
+        Ok((self.dimension()? == [0; 7]).then_some(self.value * self.factor()?))
+    }
+
+    /// Size of the unit in SI base units.
+    fn factor(&self) -> Result<f64, String> {
+        self.unit.iter().map(|(symbol, exponent)| Ok(lookup(symbol)?.0.powi(*exponent))).product()
+    }
+
+    fn dimension(&self) -> Result<Dimension, String> {
+        let mut dimension = [0; 7];
+        for (symbol, exponent) in &self.unit {
+            for (total, base) in dimension.iter_mut().zip(lookup(symbol)?.1) {
+                *total += base * exponent;
+            }
+        }
+        Ok(dimension)
+    }
+
+    /// The unit as typed after the number, e.g. `kg*m/s^^2`, or `a number` if there is none.
+    fn unit_name(&self) -> String {
+        let power = |symbol: &String, exponent: i32| match exponent {
+            1 => symbol.clone(),
+            _ => format!("{}^^{}", symbol, exponent),
+        };
+        let numerator: Vec<String> = self.unit.iter()
+            .filter(|(_, exponent)| *exponent > 0)
+            .map(|(symbol, exponent)| power(symbol, *exponent))
+            .collect();
+        let denominator: Vec<String> = self.unit.iter()
+            .filter(|(_, exponent)| *exponent < 0)
+            .map(|(symbol, exponent)| power(symbol, -exponent))
+            .collect();
+        match (numerator.is_empty(), denominator.is_empty()) {
+            (true, true) => String::from("a number"),
+            (false, _) => std::iter::once(numerator.join("*")).chain(denominator).collect::<Vec<String>>().join("/"),
+            (true, false) => self.unit.iter()
+                .map(|(symbol, exponent)| power(symbol, *exponent))
+                .collect::<Vec<String>>()
+                .join("*"),
+        }
+    }
+
+    /// The value expressed in the unit of `target`, if both measure the same dimension.
+    fn value_in(&self, target: &Quantity) -> Result<Option<f64>, String> {
+        Ok((self.dimension()? == target.dimension()?).then_some(self.value * self.factor()? / target.factor()?))
+    }
+
+    fn multiplied(&self, other: &Quantity, sign: i32) -> Quantity {
+        let mut unit = self.unit.clone();
+        for (symbol, exponent) in &other.unit {
+            match unit.iter_mut().find(|(name, _)| name == symbol) {
+                Some((_, total)) => *total += sign * exponent,
+                None => unit.push((symbol.clone(), sign * exponent)),
+            }
+        }
+        unit.retain(|(_, exponent)| *exponent != 0);
+        let value = if sign > 0 { self.value * other.value } else { self.value / other.value };
+        Quantity { value, unit }
+    }
+
+    fn power(&self, exponent: i32) -> Quantity {
+        Quantity {
+            value: self.value.powi(exponent),
+            unit: self.unit.iter().map(|(symbol, power)| (symbol.clone(), power * exponent)).collect(),
+        }
+    }
+}
+
+/// Writes the quantity the way it is typed, e.g. `2.5 m/s` or `3.2 kg*m/s^^2`.
+impl Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.unit.is_empty() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} {}", self.value, self.unit_name())
+        }
+    }
+}
+
+fn mismatch(action: &str, a: &Quantity, b: &Quantity) -> String {
+    format!("Error. Cannot {} {} and {}.", action, a.unit_name(), b.unit_name())
+}
+
+/// Applies an operator to quantities. Sums and differences convert the right
+/// operand into the unit of the left one; `in` and `to` convert the
+/// left operand into the unit on the right, which must not have a number of its own.
+pub fn apply(operator: &str, a: &Quantity, b: &Quantity) -> Result<Quantity, String> {
+    match operator {
+        "*" => Ok(a.multiplied(b, 1)),
+        "/" => Ok(a.multiplied(b, -1)),
+        "+" => {
+            let b = b.value_in(a)?.ok_or_else(|| mismatch("add", a, b))?;
+            Ok(Quantity { value: a.value + b, unit: a.unit.clone() })
+        }
+        "-" => {
+            let b = b.value_in(a)?.ok_or_else(|| mismatch("subtract", a, b))?;
+            Ok(Quantity { value: a.value - b, unit: a.unit.clone() })
+        }
+        "^^" => match b.as_number()? {
+            Some(exponent) if exponent.fract() == 0.0 && exponent.abs() <= i32::MAX as f64 => Ok(a.power(exponent as i32)),
+            _ => Err(format!("Error. Cannot raise {} to the power {}.", a.unit_name(), b)),
+        },
+        "in" | "to" if b.value != 1.0 => Err(format!("Error. Cannot convert to {}, only to a unit such as {}.", b, b.unit_name())),
+        "in" | "to" => match a.value_in(b)? {
+            Some(value) => Ok(Quantity { value, unit: b.unit.clone() }),
+            None => Err(format!("Error. Cannot convert {} to {}.", a.unit_name(), b.unit_name())),
+        },
+        _ => Err(format!("Error. {} is not defined for quantities with units.", operator)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(value: f64, symbol: &str) -> Quantity {
+        Quantity::unit(symbol).unwrap().scaled(value)
+    }
+
+    #[test]
+    fn conversions_take_a_bare_unit() {
+        assert_eq!(apply("in", &quantity(5000.0, "m"), &quantity(1.0, "km")), Ok(quantity(5.0, "km")));
+        assert!(apply("in", &quantity(5.0, "m"), &quantity(2.0, "m")).is_err());
+    }
+
+    #[test]
+    fn unknown_units_are_errors() {
+        assert_eq!(Quantity::unit("furlong"), Err(String::from("Error. Unknown unit furlong.")));
+    }
+}